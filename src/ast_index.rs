@@ -0,0 +1,134 @@
+use crate::expression::Expression;
+
+// Identifies a node within one AstIndex. Ids are handed out in pre-order, so
+// the root is always 0 and a parent's id is smaller than its children's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+// A flattened view of a parsed expression tree that can be queried by id,
+// walked upwards, and searched by source position, for tools that start
+// from a cursor rather than from the root.
+pub struct AstIndex<'a> {
+    nodes: Vec<&'a Expression>,
+    parents: Vec<Option<NodeId>>,
+    children: Vec<Vec<NodeId>>,
+}
+
+impl<'a> AstIndex<'a> {
+    pub fn new(root: &'a Expression) -> AstIndex<'a> {
+        let mut index = AstIndex {
+            nodes: Vec::new(),
+            parents: Vec::new(),
+            children: Vec::new(),
+        };
+        index.add(root, None);
+        index
+    }
+
+    fn add(&mut self, expression: &'a Expression, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(expression);
+        self.parents.push(parent);
+        self.children.push(Vec::new());
+
+        for child in expression.children() {
+            let child = self.add(child, Some(id));
+            self.children[id.0].push(child);
+        }
+        id
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, id: NodeId) -> &'a Expression {
+        self.nodes[id.0]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.parents[id.0]
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.children[id.0]
+    }
+
+    // The innermost node whose span covers the character at `offset`.
+    pub fn node_at_span(&self, offset: usize) -> Option<NodeId> {
+        let mut id = self.root();
+        if !self.node(id).span.contains(offset) {
+            return None;
+        }
+
+        while let Some(child) = self
+            .children(id)
+            .iter()
+            .find(|child| self.node(**child).span.contains(offset))
+        {
+            id = *child;
+        }
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::ExpressionKind;
+    use crate::test_util::parse;
+
+    #[test]
+    fn ids_are_pre_order() {
+        let expression = parse("(1 + 2) * -3").unwrap();
+        let index = AstIndex::new(&expression);
+
+        assert_eq!(7, index.len());
+        assert_eq!(&expression, index.node(index.root()));
+        assert_eq!("(* ((+ 1 2)) (- 3))", format!("{}", index.node(NodeId(0))));
+        assert_eq!("((+ 1 2))", format!("{}", index.node(NodeId(1))));
+        assert_eq!("(+ 1 2)", format!("{}", index.node(NodeId(2))));
+        assert_eq!("(- 3)", format!("{}", index.node(NodeId(5))));
+    }
+
+    #[test]
+    fn parents_and_children_agree() {
+        let expression = parse("1 + 2 * 3 == !nil").unwrap();
+        let index = AstIndex::new(&expression);
+
+        assert_eq!(None, index.parent(index.root()));
+        for i in 1..index.len() {
+            let id = NodeId(i);
+            let parent = index.parent(id).unwrap();
+            assert!(index.children(parent).contains(&id));
+        }
+    }
+
+    #[test]
+    fn node_at_span_finds_the_innermost_node() {
+        let source = "1 + 23 * 4";
+        let expression = parse(source).unwrap();
+        let index = AstIndex::new(&expression);
+
+        let id = index.node_at_span(5).unwrap();
+        assert_eq!(ExpressionKind::NumberLiteral(23.0), index.node(id).kind);
+        assert_eq!(
+            "(* 23 4)",
+            format!("{}", index.node(index.parent(id).unwrap()))
+        );
+
+        // The operator belongs to the binary expression around it.
+        let id = index.node_at_span(2).unwrap();
+        assert_eq!(index.root(), id);
+
+        assert_eq!(None, index.node_at_span(source.len()));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::highlight::Format;
+use colored::Color;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+// Settings read from `~/.loxrc` at startup. Every key is optional, e.g.
+//
+//   [repl]
+//   prompt = "lox>"
+//   paste_prompt = "...."
+//   prompt_color = "cyan"
+//   banner = "Welcome to Lox!"
+//   history_size = 500
+//
+//   [cli]
+//   highlight_format = "html"
+//
+// Anything not set keeps the default below.
+#[derive(Debug, PartialEq)]
+pub struct Config {
+    pub prompt: String,
+    pub paste_prompt: String,
+    pub prompt_color: Color,
+    pub banner: Option<String>,
+    pub history_size: usize,
+    // Used by `lox highlight` when no --html/--ansi flag is given.
+    pub highlight_format: Format,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            prompt: String::from(">>"),
+            paste_prompt: String::from(".."),
+            prompt_color: Color::Green,
+            banner: None,
+            history_size: 100,
+            highlight_format: Format::Ansi,
+        }
+    }
+}
+
+impl Config {
+    // Where the config file lives: `$HOME/.loxrc`.
+    pub fn path() -> Option<PathBuf> {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(".loxrc"))
+    }
+
+    // Reads the config file, falling back to the defaults when there is no
+    // file. A file that exists but is malformed is an error, so a typo is
+    // not silently ignored.
+    pub fn load() -> Result<Config, String> {
+        let path = match Config::path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let table: Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| String::from(e.message()))?;
+        let mut config = Config::default();
+
+        for (section, values) in table {
+            let values = match values {
+                Value::Table(values) => values,
+                _ => return Err(format!("`{}` should be a [section]", section)),
+            };
+
+            for (key, value) in values {
+                let name = format!("{}.{}", section, key);
+                match name.as_str() {
+                    "repl.prompt" => config.prompt = string(&name, value)?,
+                    "repl.paste_prompt" => config.paste_prompt = string(&name, value)?,
+                    "repl.prompt_color" => {
+                        config.prompt_color = string(&name, value)?
+                            .parse()
+                            .map_err(|_| format!("`{}` is not a color", name))?
+                    }
+                    "repl.banner" => config.banner = Some(string(&name, value)?),
+                    "repl.history_size" => match value {
+                        Value::Integer(n) if n >= 0 => config.history_size = n as usize,
+                        _ => return Err(format!("`{}` should be a whole number", name)),
+                    },
+                    "cli.highlight_format" => {
+                        config.highlight_format = match string(&name, value)?.as_str() {
+                            "ansi" => Format::Ansi,
+                            "html" => Format::Html,
+                            _ => return Err(format!("`{}` should be \"ansi\" or \"html\"", name)),
+                        }
+                    }
+                    _ => return Err(format!("unknown setting `{}`", name)),
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn string(name: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err(format!("`{}` should be a string", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_file_is_the_default() {
+        assert_eq!(Ok(Config::default()), Config::parse(""));
+    }
+
+    #[test]
+    fn parses_every_setting() {
+        let config = Config::parse(
+            "[repl]\n\
+             prompt = \"lox>\"\n\
+             paste_prompt = \"....\"\n\
+             prompt_color = \"bright blue\"\n\
+             banner = \"hi\"\n\
+             history_size = 5\n\
+             \n\
+             [cli]\n\
+             highlight_format = \"html\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Config {
+                prompt: String::from("lox>"),
+                paste_prompt: String::from("...."),
+                prompt_color: Color::BrightBlue,
+                banner: Some(String::from("hi")),
+                history_size: 5,
+                highlight_format: Format::Html,
+            },
+            config
+        );
+    }
+
+    #[test]
+    fn unset_keys_keep_their_defaults() {
+        let config = Config::parse("[repl]\nprompt = \">\"\n").unwrap();
+
+        assert_eq!(">", config.prompt);
+        assert_eq!(Config::default().history_size, config.history_size);
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        assert_eq!(
+            Err(String::from("unknown setting `repl.promt`")),
+            Config::parse("[repl]\npromt = \">\"\n")
+        );
+        assert_eq!(
+            Err(String::from("`repl.history_size` should be a whole number")),
+            Config::parse("[repl]\nhistory_size = -1\n")
+        );
+        assert_eq!(
+            Err(String::from("`repl.prompt_color` is not a color")),
+            Config::parse("[repl]\nprompt_color = \"plaid\"\n")
+        );
+        assert_eq!(
+            Err(String::from("`prompt` should be a [section]")),
+            Config::parse("prompt = \">\"\n")
+        );
+        assert!(Config::parse("[repl\n").is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::lox_err::LoxErr;
+#[cfg(not(feature = "ariadne"))]
+use crate::source_map::SourceId;
+use crate::source_map::SourceMap;
+use crate::token::Span;
+#[cfg(not(feature = "ariadne"))]
+use colored::*;
+
+// Renders an error against the source it came from, rustc style:
+//
+//   error: Unexpected token: '$'
+//    --> main.lox:1:5
+//     |
+//   1 | 1 + $ 2
+//     |     ^
+//
+// Errors without a span only get the header and where they happened.
+// Building with the `ariadne` feature hands the layout to that crate instead.
+#[cfg(not(feature = "ariadne"))]
+pub fn render(sources: &SourceMap, err: &LoxErr) -> String {
+    let mut output = format!("{}: {}\n", "error".red().bold(), err.message());
+    let arrow = "-->".blue().bold();
+    match (err.source(), err.span()) {
+        (Some(id), Some(span)) => output.push_str(&snippet(sources, id, span)),
+        (Some(id), None) => {
+            output.push_str(&format!(" {} {}:{}\n", arrow, sources.name(id), err.line()))
+        }
+        (None, _) => output.push_str(&format!(" {} line {}\n", arrow, err.line())),
+    }
+    output
+}
+
+#[cfg(not(feature = "ariadne"))]
+fn snippet(sources: &SourceMap, id: SourceId, span: Span) -> String {
+    let chars: Vec<char> = sources.text(id).chars().collect();
+    let start = span.start.min(chars.len());
+    let (line, column) = sources.location(id, start);
+    let line_start = start + 1 - column;
+    let line_end = chars[start..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(chars.len(), |i| start + i);
+
+    let text: String = chars[line_start..line_end].iter().collect();
+    // Keep tabs so the carets line up under the same columns as the code.
+    let indent: String = chars[line_start..start]
+        .iter()
+        .map(|c| if *c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = span.end.min(line_end).saturating_sub(start).max(1);
+
+    let gutter = " ".repeat(line.to_string().len());
+    let bar = "|".blue().bold();
+    format!(
+        "{gutter}{arrow} {name}:{line}:{column}\n\
+         {gutter} {bar}\n\
+         {number} {bar} {text}\n\
+         {gutter} {bar} {indent}{carets}\n",
+        gutter = gutter,
+        arrow = "-->".blue().bold(),
+        name = sources.name(id),
+        line = line,
+        column = column,
+        bar = bar,
+        number = line.to_string().blue().bold(),
+        text = text.trim_end_matches('\r'),
+        indent = indent,
+        carets = "^".repeat(width).red().bold(),
+    )
+}
+
+#[cfg(feature = "ariadne")]
+pub fn render(sources: &SourceMap, err: &LoxErr) -> String {
+    use ariadne::{Label, Report, ReportKind};
+
+    let id = match err.source() {
+        Some(id) => id,
+        None => return format!("Error: {}\n --> line {}\n", err.message(), err.line()),
+    };
+    let name = String::from(sources.name(id));
+
+    // Without a span, point at the start of the error's line.
+    let span = err.span().unwrap_or_else(|| {
+        // Line N starts after the (N - 1)th newline.
+        let offset = match err.line() {
+            0 | 1 => 0,
+            line => sources
+                .text(id)
+                .chars()
+                .enumerate()
+                .filter(|(_, c)| *c == '\n')
+                .nth(line - 2)
+                .map_or(0, |(i, _)| i + 1),
+        };
+        Span::new(offset, offset)
+    });
+    let range = span.start..span.end;
+
+    let mut output = Vec::new();
+    Report::build(ReportKind::Error, (name.clone(), range.clone()))
+        .with_message(err.message())
+        .with_label(Label::new((name.clone(), range)).with_message("here"))
+        .finish()
+        .write(
+            ariadne::sources(vec![(name, sources.text(id))]),
+            &mut output,
+        )
+        .ok();
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+#[cfg(all(test, not(feature = "ariadne")))]
+mod tests {
+    use super::*;
+    use crate::golden_tests::strip_ansi;
+
+    fn plain(source: &str, err: LoxErr) -> String {
+        let mut sources = SourceMap::new();
+        let id = sources.add("test.lox", source);
+        strip_ansi(&render(&sources, &err.with_source(id)))
+    }
+
+    #[test]
+    fn underlines_the_span() {
+        let err = LoxErr::new(1, String::from("Unexpected token: '$$'")).with_span(Span::new(4, 6));
+
+        assert_eq!(
+            "error: Unexpected token: '$$'\n \
+             --> test.lox:1:5\n  \
+             |\n\
+             1 | 1 + $$ 2\n  \
+             |     ^^\n",
+            plain("1 + $$ 2", err)
+        );
+    }
+
+    #[test]
+    fn shows_only_the_line_of_the_span() {
+        let source = "1 +\n\t(2\n\n// end";
+        let err = LoxErr::new(4, String::from("expected RIGHT_PAREN")).with_span(Span::new(6, 7));
+
+        assert_eq!(
+            "error: expected RIGHT_PAREN\n \
+             --> test.lox:2:3\n  \
+             |\n\
+             2 | \t(2\n  \
+             | \t ^\n",
+            plain(source, err)
+        );
+    }
+
+    #[test]
+    fn empty_spans_get_one_caret() {
+        let err = LoxErr::new(1, String::from("got EOF")).with_span(Span::new(2, 2));
+
+        assert!(plain("(1", err).ends_with("1 | (1\n  |   ^\n"));
+    }
+
+    #[test]
+    fn spans_running_past_the_line_stop_at_its_end() {
+        let err = LoxErr::new(2, String::from("Unterminated string")).with_span(Span::new(2, 9));
+
+        assert!(plain("1 \"ab\ncd", err).ends_with("1 | 1 \"ab\n  |   ^^^\n"));
+    }
+
+    #[test]
+    fn errors_without_a_span_show_the_file_and_line() {
+        let err = LoxErr::new(12, String::from("oops"));
+
+        assert_eq!("error: oops\n --> test.lox:12\n", plain("", err));
+    }
+
+    #[test]
+    fn errors_without_a_source_show_the_line() {
+        let err = LoxErr::new(12, String::from("oops"));
+
+        assert_eq!(
+            "error: oops\n --> line 12\n",
+            strip_ansi(&render(&SourceMap::new(), &err))
+        );
+    }
+}
+
+#[cfg(all(test, feature = "ariadne"))]
+mod ariadne_tests {
+    use super::*;
+    use crate::golden_tests::strip_ansi;
+
+    fn plain(source: &str, err: LoxErr) -> String {
+        let mut sources = SourceMap::new();
+        let id = sources.add("test.lox", source);
+        strip_ansi(&render(&sources, &err.with_source(id)))
+    }
+
+    #[test]
+    fn points_at_the_span() {
+        let err = LoxErr::new(2, String::from("Unexpected token: '$'")).with_span(Span::new(8, 9));
+        let output = plain("1 +\n2 + $", err);
+
+        assert!(output.contains("Unexpected token: '$'"));
+        assert!(output.contains("test.lox:2:5"));
+    }
+
+    #[test]
+    fn points_at_the_start_of_the_line_without_a_span() {
+        let output = plain("1 +\n2 +\n3", LoxErr::new(1, String::from("first")));
+        assert!(output.contains("test.lox:1:1"), "{}", output);
+
+        let output = plain("1 +\n2 +\n3", LoxErr::new(3, String::from("third")));
+        assert!(output.contains("test.lox:3:1"), "{}", output);
+    }
+}
@@ -1,8 +1,16 @@
-use crate::token::Token;
+use crate::number::format_number;
+use crate::parser::Precedence;
+use crate::token::{Span, Token};
 use std::fmt;
 
 #[derive(Debug)]
-pub enum Expression {
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExpressionKind {
     Binary {
         left: Box<Expression>,
         operator: Token,
@@ -19,16 +27,54 @@ pub enum Expression {
     NilLiteral,
 }
 
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Expression {
+        Expression { kind, span }
+    }
+
+    // The expressions directly inside this one, left to right.
+    pub fn children(&self) -> Vec<&Expression> {
+        match &self.kind {
+            ExpressionKind::Binary { left, right, .. } => vec![left, right],
+            ExpressionKind::Unary { right, .. } => vec![right],
+            ExpressionKind::Grouping(e) => vec![e],
+            ExpressionKind::NumberLiteral(_)
+            | ExpressionKind::StringLiteral(_)
+            | ExpressionKind::BoolLiteral(_)
+            | ExpressionKind::NilLiteral => vec![],
+        }
+    }
+}
+
+// Like tokens, expressions compare by structure and ignore where in the
+// source they were parsed from.
+impl PartialEq for Expression {
+    fn eq(&self, other: &Expression) -> bool {
+        self.kind == other.kind
+    }
+}
+
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.kind, f)
+    }
+}
+
+// `{}` prints the Lisp-style tree; `{:#}` prints Lox source instead.
+impl fmt::Display for ExpressionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return write_source(self, f, Precedence::Equality);
+        }
+
         match self {
-            Expression::NumberLiteral(n) => write!(f, "{}", n),
-            Expression::StringLiteral(s) => write!(f, "'{}'", s),
-            Expression::BoolLiteral(b) => write!(f, "{}", b),
-            Expression::NilLiteral => write!(f, "nil"),
-            Expression::Grouping(e) => write!(f, "({})", e),
-            Expression::Unary { operator, right } => write!(f, "({} {})", operator, right),
-            Expression::Binary {
+            ExpressionKind::NumberLiteral(n) => write!(f, "{}", format_number(*n)),
+            ExpressionKind::StringLiteral(s) => write!(f, "'{}'", s),
+            ExpressionKind::BoolLiteral(b) => write!(f, "{}", b),
+            ExpressionKind::NilLiteral => write!(f, "nil"),
+            ExpressionKind::Grouping(e) => write!(f, "({})", e),
+            ExpressionKind::Unary { operator, right } => write!(f, "({} {})", operator, right),
+            ExpressionKind::Binary {
                 left,
                 operator,
                 right,
@@ -36,3 +82,85 @@ impl fmt::Display for Expression {
         }
     }
 }
+
+// Writes `kind` as Lox source that parses back to the same tree. Groupings
+// are kept as written; other parentheses are only added where a subtree
+// binds looser than `min` (e.g. a hand-built tree with no Grouping nodes).
+fn write_source(kind: &ExpressionKind, f: &mut fmt::Formatter<'_>, min: Precedence) -> fmt::Result {
+    match kind {
+        ExpressionKind::NumberLiteral(n) => write!(f, "{}", n),
+        ExpressionKind::StringLiteral(s) => write!(f, "\"{}\"", s),
+        ExpressionKind::BoolLiteral(b) => write!(f, "{}", b),
+        ExpressionKind::NilLiteral => write!(f, "nil"),
+        ExpressionKind::Grouping(e) => {
+            write!(f, "(")?;
+            write_source(&e.kind, f, Precedence::Equality)?;
+            write!(f, ")")
+        }
+        ExpressionKind::Unary { operator, right } => {
+            write!(f, "{}", operator)?;
+            write_source(&right.kind, f, Precedence::Unary)
+        }
+        ExpressionKind::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            let precedence = Precedence::of(&operator.kind);
+            if precedence < min {
+                write!(f, "(")?;
+            }
+            write_source(&left.kind, f, precedence)?;
+            write!(f, " {} ", operator)?;
+            write_source(&right.kind, f, precedence.next())?;
+            if precedence < min {
+                write!(f, ")")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{binary, grouping, node, number, unary};
+    use crate::token::TokenKind;
+
+    #[test]
+    fn alternate_format_prints_lox_source() {
+        let sum = binary(number(1.0), TokenKind::Plus, "+", number(2.5));
+        let grouped = grouping(sum);
+        let expr = binary(
+            grouped,
+            TokenKind::EqualEqual,
+            "==",
+            node(ExpressionKind::NilLiteral),
+        );
+
+        assert_eq!("(1 + 2.5) == nil", format!("{:#}", expr));
+        assert_eq!("(== ((+ 1 2.5)) nil)", format!("{}", expr));
+    }
+
+    #[test]
+    fn alternate_format_adds_only_needed_parentheses() {
+        let sum = binary(number(1.0), TokenKind::Plus, "+", number(2.0));
+        let product = binary(sum, TokenKind::Star, "*", number(3.0));
+        assert_eq!("(1 + 2) * 3", format!("{:#}", product));
+
+        let product = binary(number(2.0), TokenKind::Star, "*", number(3.0));
+        let sum = binary(number(1.0), TokenKind::Plus, "+", product);
+        assert_eq!("1 + 2 * 3", format!("{:#}", sum));
+
+        let inner = binary(number(2.0), TokenKind::Minus, "-", number(3.0));
+        let outer = binary(number(1.0), TokenKind::Minus, "-", inner);
+        assert_eq!("1 - (2 - 3)", format!("{:#}", outer));
+
+        let negated = unary(
+            TokenKind::Minus,
+            "-",
+            binary(number(1.0), TokenKind::Plus, "+", number(2.0)),
+        );
+        assert_eq!("-(1 + 2)", format!("{:#}", negated));
+    }
+}
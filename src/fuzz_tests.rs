@@ -0,0 +1,94 @@
+// Robustness tests: nothing in the pipeline may panic, whatever the input.
+//
+// Every file in `lox_tests/fuzz` (inputs that once broke something, or look
+// like they might) and a batch of random inputs are pushed through each
+// stage: scanning with and without trivia, parsing, highlighting, token
+// dumps, diagnostics and incremental re-scanning. Outputs are not checked,
+// only that every stage returns. Add a file to the corpus whenever a new
+// crash is found.
+use crate::highlight::{self, Format};
+use crate::incremental::IncrementalScan;
+use crate::lox::Lox;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::test_util::Rng;
+use crate::token::Span;
+use crate::token_dump;
+use std::fs;
+use std::panic;
+
+const CORPUS_DIR: &str = "lox_tests/fuzz";
+
+fn exercise(source: &str) {
+    let mut scanner = Scanner::new(String::from(source));
+    let _ = scanner.scan();
+    let _ = Parser::new(scanner.tokens().to_vec()).parse();
+    token_dump::text(scanner.tokens());
+    token_dump::json(source, scanner.tokens());
+
+    let mut scanner = Scanner::with_trivia(String::from(source));
+    let _ = scanner.scan();
+    highlight::highlight(source, scanner.tokens(), Format::Html);
+    highlight::highlight(source, scanner.tokens(), Format::Ansi);
+
+    // Runs the parser and renders every diagnostic.
+    let mut lox = Lox::new(vec![], vec![]);
+    lox.check("fuzz.lox", source);
+    let _ = lox.run("fuzz.lox", source);
+}
+
+fn corpus() -> Vec<(String, String)> {
+    let mut inputs: Vec<(String, String)> = fs::read_dir(CORPUS_DIR)
+        .expect("fuzz corpus directory should exist")
+        .map(|entry| entry.unwrap().path())
+        .map(|path| {
+            let source = fs::read_to_string(&path).unwrap();
+            (path.display().to_string(), source)
+        })
+        .collect();
+    inputs.sort();
+    inputs
+}
+
+fn random_source(rng: &mut Rng) -> String {
+    let pieces = [
+        "(", ")", "-", "!", "+", "*", "/", "==", "<=", "1", "2.5", ".", "\"", "\"s\"", "nil",
+        "true", "x", " ", "\n", "\r\n", "\t", "//", "#!", "$", "&", "é", "\u{0}", "\u{feff}",
+    ];
+    let length = rng.below(40);
+    (0..length)
+        .map(|_| pieces[rng.below(pieces.len())])
+        .collect()
+}
+
+#[test]
+fn corpus_does_not_panic() {
+    for (name, source) in corpus() {
+        if panic::catch_unwind(|| exercise(&source)).is_err() {
+            panic!("{} panicked", name);
+        }
+    }
+}
+
+#[test]
+fn random_input_does_not_panic() {
+    let mut rng = Rng::new(0x0123_4567_89ab_cdef);
+    for _ in 0..2000 {
+        exercise(&random_source(&mut rng));
+    }
+}
+
+#[test]
+fn random_edits_do_not_panic() {
+    let mut rng = Rng::new(0xfedc_ba98_7654_3210);
+    let mut scan = IncrementalScan::new(random_source(&mut rng), true);
+
+    for _ in 0..500 {
+        // Spans may point past the end of the source.
+        let length = scan.source().chars().count() + 3;
+        let start = rng.below(length);
+        let end = start + rng.below(4);
+        let text: String = random_source(&mut rng).chars().take(8).collect();
+        scan.edit(Span::new(start, end), &text);
+    }
+}
@@ -0,0 +1,122 @@
+// Golden-file tests for the scanner and parser.
+//
+// Every `lox_tests/golden/<name>.lox` is scanned and parsed, and the token
+// dump, JSON token dump and AST print are compared against `<name>.tokens`,
+// `<name>.json` and `<name>.ast` next to it. Run with `LOX_BLESS=1 cargo
+// test` to (re)write the expected files after an intentional change, then
+// review the diff.
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::token_dump;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const GOLDEN_DIR: &str = "lox_tests/golden";
+
+fn dump_tokens(source: &str) -> String {
+    let mut scanner = Scanner::new(String::from(source));
+    let errors = match scanner.scan() {
+        Ok(_) => vec![],
+        Err(errs) => errs,
+    };
+
+    let mut dump = token_dump::text(scanner.tokens());
+    for err in errors {
+        dump.push_str(&format!("{}\n", err));
+    }
+    dump
+}
+
+fn dump_json(source: &str) -> String {
+    let mut scanner = Scanner::new(String::from(source));
+    let _ = scanner.scan();
+    token_dump::json(source, scanner.tokens())
+}
+
+fn dump_ast(source: &str) -> String {
+    let mut scanner = Scanner::new(String::from(source));
+    match scanner.scan() {
+        Ok(tokens) => match Parser::new(tokens.to_vec()).parse() {
+            Ok(expression) => format!("{}\n", expression),
+            Err(err) => format!("{}\n", err),
+        },
+        Err(errs) => errs.iter().map(|err| format!("{}\n", err)).collect(),
+    }
+}
+
+// Error messages embed colored output; snapshots should not depend on
+// whether the test runner has a terminal.
+pub(crate) fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            for c in chars.by_ref() {
+                if c == 'm' {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+fn golden_inputs() -> Vec<PathBuf> {
+    let mut inputs: Vec<PathBuf> = fs::read_dir(GOLDEN_DIR)
+        .expect("golden directory should exist")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    inputs.sort();
+    inputs
+}
+
+// Returns a description of the mismatch, if any.
+fn check(path: &Path, actual: &str) -> Option<String> {
+    if env::var("LOX_BLESS").is_ok() {
+        fs::write(path, actual).unwrap();
+        return None;
+    }
+
+    match fs::read_to_string(path) {
+        Ok(expected) if expected == actual => None,
+        Ok(expected) => Some(format!(
+            "{} differs\n--- expected\n{}--- actual\n{}",
+            path.display(),
+            expected,
+            actual
+        )),
+        Err(_) => Some(format!(
+            "{} is missing, run with LOX_BLESS=1 to create it",
+            path.display()
+        )),
+    }
+}
+
+#[test]
+fn golden_files() {
+    let mut failures = vec![];
+
+    for input in golden_inputs() {
+        let source = fs::read_to_string(&input).unwrap();
+
+        let tokens = strip_ansi(&dump_tokens(&source));
+        failures.extend(check(&input.with_extension("tokens"), &tokens));
+
+        let json = dump_json(&source);
+        failures.extend(check(&input.with_extension("json"), &json));
+
+        let ast = strip_ansi(&dump_ast(&source));
+        failures.extend(check(&input.with_extension("ast"), &ast));
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn strip_ansi_removes_escape_sequences() {
+    assert_eq!("plain bold", strip_ansi("plain \u{1b}[1mbold\u{1b}[0m"));
+}
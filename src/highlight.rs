@@ -0,0 +1,175 @@
+use crate::token::{Token, TokenKind};
+use colored::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Html,
+    Ansi,
+}
+
+// Renders `source` with each token styled by kind. Expects the tokens of a
+// Scanner::with_trivia() scan, which cover the whole source; anything a
+// token stream does leave out is copied through unstyled.
+pub fn highlight(source: &str, tokens: &[Token], format: Format) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut output = String::new();
+    let mut position = 0;
+
+    if format == Format::Html {
+        output.push_str("<pre class=\"lox\">");
+    }
+
+    for token in tokens {
+        if token.kind == TokenKind::Eof {
+            break;
+        }
+
+        let gap = slice(&chars, position, token.span.start);
+        output.push_str(&render_plain(&gap, format));
+
+        let text = slice(&chars, token.span.start, token.span.end);
+        output.push_str(&render_token(&token.kind, &text, format));
+        position = position.max(token.span.end);
+    }
+
+    let rest = slice(&chars, position, chars.len());
+    output.push_str(&render_plain(&rest, format));
+
+    if format == Format::Html {
+        output.push_str("</pre>\n");
+    }
+    output
+}
+
+// Tokens from some other source could point anywhere; out of range spans
+// just render nothing.
+fn slice(chars: &[char], start: usize, end: usize) -> String {
+    chars
+        .get(start..end)
+        .map(|chars| chars.iter().collect())
+        .unwrap_or_default()
+}
+
+fn render_token(kind: &TokenKind, text: &str, format: Format) -> String {
+    if *kind == TokenKind::Whitespace {
+        return render_plain(text, format);
+    }
+
+    let category = category(kind);
+    match format {
+        Format::Html => format!(
+            "<span class=\"lox-{} lox-{}\">{}</span>",
+            category,
+            css_name(kind),
+            escape_html(text)
+        ),
+        Format::Ansi => match category {
+            "keyword" => text.magenta().bold().to_string(),
+            "string" => text.green().to_string(),
+            "number" => text.cyan().to_string(),
+            "operator" => text.yellow().to_string(),
+            "comment" => text.dimmed().to_string(),
+            "error" => text.red().underline().to_string(),
+            _ => text.to_string(),
+        },
+    }
+}
+
+fn render_plain(text: &str, format: Format) -> String {
+    match format {
+        Format::Html => escape_html(text),
+        Format::Ansi => text.to_string(),
+    }
+}
+
+fn category(kind: &TokenKind) -> &'static str {
+    match kind {
+        kind if kind.is_keyword() => "keyword",
+        TokenKind::Str => "string",
+        TokenKind::Number => "number",
+        TokenKind::Identifier => "identifier",
+        TokenKind::Comment => "comment",
+        TokenKind::Whitespace => "whitespace",
+        TokenKind::Error => "error",
+        TokenKind::LeftParen
+        | TokenKind::RightParen
+        | TokenKind::LeftBrace
+        | TokenKind::RightBrace
+        | TokenKind::Comma
+        | TokenKind::Dot
+        | TokenKind::Semicolon
+        | TokenKind::Eof => "punctuation",
+        _ => "operator",
+    }
+}
+
+// LeftParen -> left-paren
+fn css_name(kind: &TokenKind) -> String {
+    let mut name = String::new();
+    for (i, c) in format!("{:?}", kind).chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                name.push('-');
+            }
+            name.push(c.to_ascii_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn html(source: &str) -> String {
+        let mut scanner = Scanner::with_trivia(String::from(source));
+        let _ = scanner.scan();
+        highlight(source, scanner.tokens(), Format::Html)
+    }
+
+    #[test]
+    fn html_wraps_tokens_in_classed_spans() {
+        assert_eq!(
+            "<pre class=\"lox\"><span class=\"lox-keyword lox-print\">print</span> \
+             <span class=\"lox-string lox-str\">&quot;hi&quot;</span>\
+             <span class=\"lox-punctuation lox-semicolon\">;</span></pre>\n",
+            html("print \"hi\";")
+        );
+    }
+
+    #[test]
+    fn html_keeps_comments_and_whitespace() {
+        assert_eq!(
+            "<pre class=\"lox\"><span class=\"lox-number lox-number\">1</span> \
+             <span class=\"lox-operator lox-less-equal\">&lt;=</span> \
+             <span class=\"lox-number lox-number\">2</span> \
+             <span class=\"lox-comment lox-comment\">// ok</span>\n</pre>\n",
+            html("1 <= 2 // ok\n")
+        );
+    }
+
+    #[test]
+    fn html_marks_unknown_characters() {
+        assert_eq!(
+            "<pre class=\"lox\"><span class=\"lox-error lox-error\">$</span>\
+             <span class=\"lox-identifier lox-identifier\">a</span></pre>\n",
+            html("$a")
+        );
+    }
+
+    #[test]
+    fn css_name_is_kebab_case() {
+        assert_eq!("left-paren", css_name(&TokenKind::LeftParen));
+        assert_eq!("eof", css_name(&TokenKind::Eof));
+    }
+}
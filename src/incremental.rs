@@ -0,0 +1,204 @@
+use crate::scanner::Scanner;
+use crate::token::{Span, Token};
+
+// The scanner looks at most two characters past the end of a token (the
+// '.' and digit after a number), so a token ending this close to an edit
+// may scan differently afterwards.
+const LOOKAHEAD: usize = 2;
+
+// Keeps a source text and its tokens in step across edits, re-scanning only
+// the region around each edit and reusing the old tokens after it.
+pub struct IncrementalScan {
+    source: String,
+    tokens: Vec<Token>,
+    trivia: bool,
+}
+
+impl IncrementalScan {
+    pub fn new(source: String, trivia: bool) -> IncrementalScan {
+        let mut scanner = if trivia {
+            Scanner::with_trivia(source.clone())
+        } else {
+            Scanner::new(source.clone())
+        };
+        let _ = scanner.scan();
+
+        IncrementalScan {
+            tokens: scanner.tokens().to_vec(),
+            source,
+            trivia,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    // Replaces the characters in `span` with `text`. Returns how many tokens
+    // had to be scanned again; everything else was shifted into place.
+    pub fn edit(&mut self, span: Span, text: &str) -> usize {
+        let mut chars: Vec<char> = self.source.chars().collect();
+        // A span reaching past the end edits up to the end.
+        let end = span.end.min(chars.len());
+        let span = Span::new(span.start.min(end), end);
+        let removed: Vec<char> = chars.splice(span.start..span.end, text.chars()).collect();
+        let inserted = text.chars().count();
+        let edit_end = span.start + inserted;
+        let line_delta =
+            count_lines(text.chars()) as isize - count_lines(removed.iter().cloned()) as isize;
+
+        // Restart at the end of the last token the edit cannot have touched.
+        let first = self
+            .tokens
+            .iter()
+            .position(|t| t.span.end + LOOKAHEAD > span.start)
+            .unwrap_or(self.tokens.len());
+        let restart = if first == 0 {
+            0
+        } else {
+            self.tokens[first - 1].span.end
+        };
+        let line = 1 + count_lines(chars[..restart].iter().cloned());
+
+        let source: String = chars.iter().collect();
+        let mut scanner = Scanner::resume(source.clone(), restart, line, self.trivia);
+        let mut tokens = self.tokens[..first].to_vec();
+        let mut scanned = 0;
+
+        loop {
+            let more = scanner.step().is_some();
+
+            while scanned < scanner.tokens().len() {
+                let token = &scanner.tokens()[scanned];
+                scanned += 1;
+
+                // Past the edit, a token starting where an old token started
+                // means the scanner is back in step with the old scan.
+                if token.span.start >= edit_end {
+                    let old_start = token.span.start - inserted + removed.len();
+                    if let Ok(j) = self
+                        .tokens
+                        .binary_search_by_key(&old_start, |t| t.span.start)
+                    {
+                        tokens.extend(self.tokens[j..].iter().map(|t| {
+                            shift(t, inserted as isize - removed.len() as isize, line_delta)
+                        }));
+                        self.tokens = tokens;
+                        self.source = source;
+                        return scanned - 1;
+                    }
+                }
+                tokens.push(token.clone());
+            }
+
+            if !more {
+                break;
+            }
+        }
+
+        self.tokens = tokens;
+        self.source = source;
+        scanned
+    }
+}
+
+fn count_lines(chars: impl Iterator<Item = char>) -> usize {
+    chars.filter(|c| *c == '\n').count()
+}
+
+fn shift(token: &Token, offset: isize, lines: isize) -> Token {
+    let mut token = token.clone();
+    token.span = Span::new(
+        (token.span.start as isize + offset) as usize,
+        (token.span.end as isize + offset) as usize,
+    );
+    token.line = (token.line as isize + lines) as usize;
+    token
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+
+    // Tokens compare by kind and lexeme only, so check positions as well.
+    fn positions(tokens: &[Token]) -> Vec<(String, usize, usize, usize)> {
+        tokens
+            .iter()
+            .map(|t| (format!("{:?}", t.kind), t.line, t.span.start, t.span.end))
+            .collect()
+    }
+
+    fn assert_matches_full_scan(scan: &IncrementalScan) {
+        let full = IncrementalScan::new(String::from(scan.source()), scan.trivia);
+        assert_eq!(full.tokens(), scan.tokens(), "source: {:?}", scan.source());
+        assert_eq!(positions(full.tokens()), positions(scan.tokens()));
+    }
+
+    #[test]
+    fn edit_in_the_middle_reuses_the_tail() {
+        let mut scan = IncrementalScan::new(String::from("a + b\nc + d\ne + f"), false);
+        let rescanned = scan.edit(Span::new(6, 7), "cat");
+
+        assert_eq!("a + b\ncat + d\ne + f", scan.source());
+        // "b" sits inside the lookahead window, so it is scanned again too.
+        assert_eq!(2, rescanned);
+        assert_matches_full_scan(&scan);
+    }
+
+    #[test]
+    fn inserting_lines_shifts_later_tokens() {
+        let mut scan = IncrementalScan::new(String::from("1\n2\n3"), true);
+        scan.edit(Span::new(1, 1), "\n\n");
+
+        assert_eq!(5, scan.tokens().last().unwrap().line);
+        assert_matches_full_scan(&scan);
+    }
+
+    #[test]
+    fn edit_can_merge_with_previous_token() {
+        let mut scan = IncrementalScan::new(String::from("1. + x"), false);
+        scan.edit(Span::new(2, 2), "5");
+
+        assert_eq!("1.5", scan.tokens()[0].lexeme);
+        assert_matches_full_scan(&scan);
+    }
+
+    #[test]
+    fn closing_a_string_rescans_to_the_end() {
+        let mut scan = IncrementalScan::new(String::from("1 \"abc + 2"), false);
+        scan.edit(Span::new(6, 6), "\"");
+
+        assert_eq!("abc", scan.tokens()[1].lexeme);
+        assert_matches_full_scan(&scan);
+    }
+
+    #[test]
+    fn random_edits_match_a_full_scan() {
+        let fragments = [
+            "", " ", "\n", "//", "\"", "1", ".", "5", "a", "and", "=", "!", "/", "(", "$",
+        ];
+        let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+
+        for trivia in [false, true].iter() {
+            let mut scan = IncrementalScan::new(
+                String::from("var x = 1.5; // hi\nprint \"a\nb\" != x;\n"),
+                *trivia,
+            );
+
+            for _ in 0..300 {
+                let length = scan.source().chars().count();
+                let start = rng.below(length + 1);
+                let end = start + rng.below(length - start + 1).min(3);
+                let text = fragments[rng.below(fragments.len())];
+
+                scan.edit(Span::new(start, end), text);
+                assert_matches_full_scan(&scan);
+            }
+        }
+    }
+}
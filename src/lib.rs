@@ -0,0 +1,51 @@
+pub mod source_map;
+
+pub mod lox_err;
+
+pub mod diagnostic;
+
+pub mod token;
+
+// These run on whatever source they are given, so indexing goes through
+// get() and bad input turns into a LoxErr instead of a panic. Their tests
+// are free to index.
+#[cfg_attr(not(test), deny(clippy::indexing_slicing))]
+pub mod scanner;
+
+#[cfg_attr(not(test), deny(clippy::indexing_slicing))]
+pub mod token_cursor;
+
+#[cfg_attr(not(test), deny(clippy::indexing_slicing))]
+pub mod parser;
+
+pub mod incremental;
+
+pub mod number;
+
+pub mod expression;
+
+pub mod ast_index;
+
+pub mod transform;
+
+pub mod highlight;
+
+pub mod token_dump;
+
+pub mod watch;
+
+pub mod repl;
+
+pub mod config;
+
+pub mod lox;
+pub use crate::lox::Lox;
+
+#[cfg(test)]
+mod test_util;
+
+#[cfg(test)]
+mod golden_tests;
+
+#[cfg(test)]
+mod fuzz_tests;
@@ -0,0 +1,326 @@
+use crate::diagnostic;
+use crate::highlight::{self, Format};
+use crate::lox_err::LoxErr;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::source_map::{SourceId, SourceMap};
+use crate::token_dump;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+// Runs Lox source and writes everything it produces to `out`, and every
+// diagnostic to `err`. The CLI passes stdout/stderr; tests and embedders
+// can pass any other sink (e.g. a Vec<u8>) to capture the output.
+//
+// Each source is given a name (a file path, or e.g. `<repl>`) and kept in
+// a SourceMap, so errors can be reported against the right text later.
+//
+// Write errors on either sink are ignored, as with print in jlox: a closed
+// pipe should not bring the host down.
+//
+// A Lox owns all of its state; nothing is shared between instances. With
+// Send sinks it is Send, so independent interpreters can run on separate
+// threads.
+pub struct Lox<O: Write, E: Write> {
+    out: O,
+    err: E,
+    sources: SourceMap,
+    timings: Vec<(&'static str, Duration)>,
+}
+
+impl<O: Write, E: Write> Lox<O, E> {
+    pub fn new(out: O, err: E) -> Lox<O, E> {
+        Lox {
+            out,
+            err,
+            sources: SourceMap::new(),
+            timings: Vec::new(),
+        }
+    }
+
+    pub fn out(&mut self) -> &mut O {
+        &mut self.out
+    }
+
+    pub fn err(&mut self) -> &mut E {
+        &mut self.err
+    }
+
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    // Runs `source` one stage at a time, stopping at the first stage that
+    // fails and returning its errors, tagged with the source they came from.
+    pub fn run(&mut self, name: &str, source: &str) -> Result<(), Vec<LoxErr>> {
+        let id = self.sources.add(name, source);
+        self.timings.clear();
+
+        let started = Instant::now();
+        let mut scanner = Scanner::new(source.to_string());
+        let scanned = scanner.scan().map(|tokens| tokens.to_vec());
+        self.timings.push(("scan", started.elapsed()));
+        let tokens = scanned.map_err(|errs| tag(id, errs))?;
+
+        let started = Instant::now();
+        let parsed = Parser::new(tokens).parse();
+        self.timings.push(("parse", started.elapsed()));
+        let expression = parsed.map_err(|err| tag(id, vec![err]))?;
+
+        let _ = writeln!(self.out, "Parsed: {}", expression);
+        Ok(())
+    }
+
+    // How long each stage of the last run took, in the order they ran. A
+    // stage that was never reached has no entry.
+    pub fn timings(&self) -> &[(&'static str, Duration)] {
+        &self.timings
+    }
+
+    // The one entry point for running code, whether it came from a file, the
+    // REPL or the command line: runs `source` and reports any errors.
+    // Returns whether it ran cleanly.
+    pub fn run_source(&mut self, name: &str, source: &str) -> bool {
+        match self.run(name, source) {
+            Ok(()) => true,
+            Err(errs) => {
+                self.report(&errs);
+                false
+            }
+        }
+    }
+
+    // Scans and parses `source` without running it, reporting every error
+    // found. Returns whether it was clean.
+    pub fn check(&mut self, name: &str, source: &str) -> bool {
+        let id = self.sources.add(name, source);
+        let mut scanner = Scanner::new(String::from(source));
+        let errs = match scanner.scan() {
+            Ok(tokens) => match Parser::new(tokens.to_vec()).parse() {
+                Ok(_) => vec![],
+                Err(err) => vec![err],
+            },
+            Err(errs) => errs,
+        };
+
+        let clean = errs.is_empty();
+        self.report(&tag(id, errs));
+        clean
+    }
+
+    // Writes the tokens of `source`, as JSON or as one line per token.
+    // Returns whether it scanned without errors.
+    pub fn tokens(&mut self, name: &str, source: &str, json: bool) -> bool {
+        let id = self.sources.add(name, source);
+        let mut scanner = Scanner::new(String::from(source));
+        let errs = scanner.scan().err().unwrap_or_default();
+
+        let dump = if json {
+            token_dump::json(source, scanner.tokens())
+        } else {
+            token_dump::text(scanner.tokens())
+        };
+        let _ = write!(self.out, "{}", dump);
+
+        let clean = errs.is_empty();
+        self.report(&tag(id, errs));
+        clean
+    }
+
+    pub fn highlight(&mut self, name: &str, source: &str, format: Format) {
+        let id = self.sources.add(name, source);
+        let mut scanner = Scanner::with_trivia(String::from(source));
+        if let Err(errs) = scanner.scan() {
+            self.report(&tag(id, errs));
+        }
+        let highlighted = highlight::highlight(source, scanner.tokens(), format);
+        let _ = write!(self.out, "{}", highlighted);
+    }
+
+    // Writes each error as a diagnostic, showing the source it points into
+    // when that source is one this Lox has run.
+    pub fn report(&mut self, errs: &[LoxErr]) {
+        for err in errs {
+            let _ = writeln!(self.err, "{}", diagnostic::render(&self.sources, err));
+        }
+    }
+}
+
+fn tag(id: SourceId, errs: Vec<LoxErr>) -> Vec<LoxErr> {
+    errs.into_iter().map(|err| err.with_source(id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captured(output: &[u8]) -> String {
+        String::from_utf8(output.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn lox_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Lox<Vec<u8>, Vec<u8>>>();
+        assert_send::<Lox<std::io::Stdout, std::io::Stderr>>();
+    }
+
+    #[test]
+    fn instances_run_independently_on_threads() {
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let mut lox = Lox::new(vec![], vec![]);
+                    for j in 0..50 {
+                        lox.run(&format!("thread{}.lox", i), &format!("{} + {}", i, j))
+                            .unwrap();
+                    }
+                    assert!(!lox.run_source(&format!("thread{}.lox", i), "("));
+                    (captured(&lox.out), captured(&lox.err))
+                })
+            })
+            .collect();
+
+        for (i, handle) in handles.into_iter().enumerate() {
+            let (out, err) = handle.join().unwrap();
+            assert!(out.contains(&format!("Parsed: (+ {} 49)\n", i)));
+            assert_eq!(50, out.matches("Parsed").count());
+            assert!(err.contains(&format!("thread{}.lox", i)));
+        }
+    }
+
+    #[test]
+    fn run_writes_to_out() {
+        let mut lox = Lox::new(vec![], vec![]);
+        lox.run("test.lox", "1 + 2").unwrap();
+
+        assert_eq!("Parsed: (+ 1 2)\n", captured(&lox.out));
+        assert!(lox.err.is_empty());
+    }
+
+    #[test]
+    fn parse_errors_are_returned() {
+        let mut lox = Lox::new(vec![], vec![]);
+        let errs = lox.run("test.lox", "(1").unwrap_err();
+
+        assert_eq!(1, errs.len());
+        assert!(errs[0].message().contains("Unexpected token"));
+        assert!(lox.out.is_empty());
+        assert!(lox.err.is_empty());
+    }
+
+    #[test]
+    fn run_source_reports_errors_from_any_stage() {
+        let mut lox = Lox::new(vec![], vec![]);
+
+        assert!(lox.run_source("test.lox", "1 + 2"));
+        assert!(!lox.run_source("test.lox", "1 $ 2"));
+        assert!(!lox.run_source("test.lox", "(1"));
+
+        assert_eq!(1, captured(&lox.out).matches("Parsed").count());
+        let err = captured(&lox.err);
+        assert!(err.contains("'$'"));
+        assert!(err.contains("Unexpected token"));
+    }
+
+    #[test]
+    fn scan_errors_are_returned() {
+        let mut lox = Lox::new(vec![], vec![]);
+        let errs = lox.run("test.lox", "1 $ 2").unwrap_err();
+
+        assert_eq!(1, errs.len());
+        assert!(lox.out.is_empty());
+        assert_eq!("test.lox", lox.sources().name(errs[0].source().unwrap()));
+    }
+
+    #[test]
+    fn running_a_name_again_reuses_its_source() {
+        let mut lox = Lox::new(vec![], vec![]);
+        for i in 0..10 {
+            lox.run_source("<repl>", &format!("{} +", i));
+        }
+        lox.check("other.lox", "1");
+        let errs = lox.run("<repl>", "10 +").unwrap_err();
+
+        assert_eq!(2, lox.sources().len());
+        assert_eq!("10 +", lox.sources().text(errs[0].source().unwrap()));
+    }
+
+    #[test]
+    fn run_times_each_stage_it_reaches() {
+        let mut lox = Lox::new(vec![], vec![]);
+        let stages = |lox: &Lox<Vec<u8>, Vec<u8>>| -> Vec<&str> {
+            lox.timings().iter().map(|(stage, _)| *stage).collect()
+        };
+
+        lox.run_source("test.lox", "1 + 2");
+        assert_eq!(vec!["scan", "parse"], stages(&lox));
+
+        lox.run_source("test.lox", "1 $ 2");
+        assert_eq!(vec!["scan"], stages(&lox));
+    }
+
+    #[test]
+    fn errors_name_the_source_they_came_from() {
+        let mut lox = Lox::new(vec![], vec![]);
+        lox.run_source("first.lox", "1");
+        lox.run_source("second.lox", "(1");
+
+        let err = captured(&lox.err);
+        assert!(err.contains("second.lox"));
+        assert!(!err.contains("first.lox"));
+    }
+
+    #[test]
+    fn check_reports_without_running() {
+        let mut lox = Lox::new(vec![], vec![]);
+
+        assert!(lox.check("good.lox", "1 + 2"));
+        assert!(lox.out.is_empty());
+        assert!(lox.err.is_empty());
+
+        assert!(!lox.check("bad.lox", "1 $ 2 # 3"));
+        let err = captured(&lox.err);
+        assert!(err.contains("'$'"));
+        assert!(err.contains("'#'"));
+
+        assert!(!lox.check("bad.lox", "(1"));
+        assert!(lox.out.is_empty());
+
+        assert!(!lox.check("bad.lox", "1 2 3"));
+        assert!(captured(&lox.err).contains("Expected end of expression"));
+    }
+
+    #[test]
+    fn tokens_dumps_the_stream() {
+        let mut lox = Lox::new(vec![], vec![]);
+
+        assert!(lox.tokens("test.lox", "nil", false));
+        assert_eq!("1 NIL \"nil\"\n1 EOF \"\"\n", captured(&lox.out));
+
+        assert!(!lox.tokens("test.lox", "$", true));
+        assert!(captured(&lox.out).contains("\"kind\": \"ERROR\""));
+        assert!(captured(&lox.err).contains("'$'"));
+    }
+
+    #[test]
+    fn report_writes_each_error() {
+        let mut lox = Lox::new(vec![], vec![]);
+        lox.report(&[
+            LoxErr::new(1, String::from("first")),
+            LoxErr::new(2, String::from("second")),
+        ]);
+
+        let err = captured(&lox.err);
+        assert!(err.contains("first"));
+        assert!(err.contains("second"));
+    }
+
+    #[test]
+    fn highlight_writes_to_out() {
+        let mut lox = Lox::new(vec![], vec![]);
+        lox.highlight("test.lox", "nil", Format::Html);
+
+        assert!(captured(&lox.out).contains("lox-nil"));
+    }
+}
@@ -1,19 +1,54 @@
+use crate::source_map::SourceId;
+use crate::token::Span;
 use std::fmt;
 
 #[derive(Debug)]
 pub struct LoxErr {
     line: usize,
     message: String,
+    span: Option<Span>,
+    source: Option<SourceId>,
 }
 
 impl LoxErr {
     pub fn new(line: usize, message: String) -> LoxErr {
         LoxErr {
-            line: line,
-            message: message,
+            line,
+            message,
+            span: None,
+            source: None,
         }
     }
 
+    // Points the error at the source it is about, so diagnostics can show
+    // the offending code and not just the line number.
+    pub fn with_span(mut self, span: Span) -> LoxErr {
+        self.span = Some(span);
+        self
+    }
+
+    // Records which source text the span (or line) refers to.
+    pub fn with_source(mut self, source: SourceId) -> LoxErr {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn source(&self) -> Option<SourceId> {
+        self.source
+    }
+
     pub fn display_message(&self) -> String {
         format!("[Line {}] Error: {}", self.line, self.message)
     }
@@ -35,12 +70,23 @@ mod tests {
         let expected_err = LoxErr {
             line: 11,
             message: String::from("testing..."),
+            span: None,
+            source: None,
         };
 
         assert_eq!(error.line, expected_err.line);
         assert_eq!(error.message, expected_err.message);
     }
 
+    #[test]
+    fn with_span() {
+        let error = LoxErr::new(2, String::from("testing...")).with_span(Span::new(4, 6));
+
+        assert_eq!(Some(Span::new(4, 6)), error.span());
+        assert_eq!(2, error.line());
+        assert_eq!("testing...", error.message());
+    }
+
     #[test]
     fn display_message() {
         let error = LoxErr::new(11, String::from("testing..."));
@@ -1,128 +1,240 @@
 use colored::*;
 use std::env::args;
-use std::fs::File;
-use std::io::{self, Read, Write};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use lox::config::Config;
+use lox::highlight;
+use lox::repl::{self, Input, LineReader};
+use lox::source_map;
+use lox::watch::{Change, Watcher};
+use lox::Lox;
+
+type StdLox = Lox<io::Stdout, io::Stderr>;
+
+// Exit codes, as in jlox (and sysexits.h).
+const EX_USAGE: i32 = 64; // the command line was wrong
+const EX_DATAERR: i32 = 65; // the source has errors
+const EX_NOINPUT: i32 = 66; // an input file could not be read
+
+fn exit(result: Result<(), i32>) -> ! {
+    process::exit(match result {
+        Ok(()) => 0,
+        Err(code) => code,
+    });
+}
 
-mod lox_err;
-use lox_err::LoxErr;
+fn read_file(lox: &mut StdLox, fname: &str) -> Option<String> {
+    let program = fs::read(fname)
+        .map_err(|e| e.to_string())
+        .and_then(source_map::decode);
+    match program {
+        Ok(program) => Some(program),
+        Err(e) => {
+            let _ = writeln!(lox.err(), "File read error: {}: {}", fname, e);
+            None
+        }
+    }
+}
 
-mod token;
-use token::{Token, TokenKind};
+fn run_file(lox: &mut StdLox, fname: &str) -> Result<(), i32> {
+    let program = read_file(lox, fname).ok_or(EX_NOINPUT)?;
+    run_program(lox, fname, &program)
+}
 
-mod scanner;
-use scanner::Scanner;
+fn run_program(lox: &mut StdLox, name: &str, program: &str) -> Result<(), i32> {
+    if lox.run_source(name, program) {
+        Ok(())
+    } else {
+        Err(EX_DATAERR)
+    }
+}
 
-mod expression;
-use expression::Expression;
+// Runs `fname` and then writes how long each stage of the run took.
+// Nothing is reported when the file can't be read.
+fn time_file(lox: &mut StdLox, fname: &str) -> Result<(), i32> {
+    let program = read_file(lox, fname).ok_or(EX_NOINPUT)?;
+    let result = run_program(lox, fname, &program);
 
-mod parser;
-use parser::Parser;
+    let timings = lox.timings().to_vec();
+    let total: Duration = timings.iter().map(|(_, time)| *time).sum();
+    for (stage, time) in timings {
+        let _ = writeln!(lox.err(), "{:<8}{:>12.3?}", stage, time);
+    }
+    let _ = writeln!(lox.err(), "{:<8}{:>12.3?}", "total", total);
+    result
+}
 
-fn run(statement: &str) -> Result<bool, Vec<LoxErr>> {
-    let mut scanner = Scanner::new(statement.to_string());
+// Runs `fname` again every time it is saved, on a cleared screen, until
+// the process is interrupted.
+fn watch_file(lox: &mut StdLox, fname: &str) {
+    if let Err(e) = fs::metadata(fname) {
+        let _ = writeln!(lox.err(), "Cannot watch {}: {}", fname, e);
+        process::exit(EX_NOINPUT);
+    }
 
-    match scanner.scan() {
-        Err(errs) => Err(errs),
-        Ok(tokens) => {
-            println!("{:?}", tokens);
-            let mut parser = Parser::new(tokens.to_vec());
-            match parser.parse() {
-                Ok(expression) => println!("Parsed: {}", expression),
-                Err(err) => eprintln!("{}", format!("{}", err).red()),
+    let mut watcher = Watcher::new(fname);
+    loop {
+        match watcher.changed() {
+            Change::Modified => {
+                let _ = write!(lox.out(), "\x1b[2J\x1b[H");
+                let _ = writeln!(lox.out(), "{} {}", "watching".green().bold(), fname);
+                let _ = run_file(lox, fname);
+                let _ = lox.out().flush();
             }
-            Ok(true)
+            Change::Removed => {
+                let _ = writeln!(lox.err(), "{} is gone; waiting for it to come back", fname);
+            }
+            Change::Unchanged => {}
         }
+        thread::sleep(Duration::from_millis(250));
     }
 }
 
-fn run_file(fname: &String) {
-    let file = File::open(fname);
-
-    match file {
-        Ok(mut file) => {
-            let mut program = String::new();
-            file.read_to_string(&mut program).unwrap();
-            let source = program.trim_end();
-
-            let mut scanner = Scanner::new(String::from(source));
-            match scanner.scan() {
-                Err(errs) => {
-                    for err in errs {
-                        eprintln!("{}", format!("{}", err).red())
-                    }
-                }
-                _ => println!("{:?}", scanner),
-            }
-        }
-        Err(e) => eprintln!("File read error: {}", e),
-    }
+fn highlight_file(lox: &mut StdLox, fname: &str, format: highlight::Format) -> Result<(), i32> {
+    let program = read_file(lox, fname).ok_or(EX_NOINPUT)?;
+    lox.highlight(fname, &program, format);
+    Ok(())
 }
 
-fn run_interpreter() {
+fn run_interpreter(lox: &mut StdLox, config: &Config) {
+    let mut reader = LineReader::new(config.history_size);
+    let _bracketed_paste = io::stdout()
+        .is_terminal()
+        .then(repl::BracketedPaste::enable);
+
+    if let Some(banner) = &config.banner {
+        let _ = writeln!(lox.out(), "{}", banner);
+    }
+
     loop {
-        print!("{} ", ">>".green().bold());
-        io::stdout().flush().unwrap();
-
-        let mut statement = String::new();
-
-        match io::stdin().read_line(&mut statement) {
-            Ok(_) => {
-                let statement = statement.trim_end();
-
-                if statement == "exit" {
-                    println!("\n{}", "bye!!".green());
-                    return;
-                } else {
-                    match run(statement) {
-                        Ok(_) => println!("{}", statement),
-                        Err(errs) => {
-                            for err in errs {
-                                eprintln!("{}", err);
-                            }
-                            break;
-                        }
-                    }
+        let prompt = if reader.in_paste() {
+            &config.paste_prompt
+        } else {
+            &config.prompt
+        };
+        let _ = write!(lox.out(), "{} ", prompt.color(config.prompt_color).bold());
+        let _ = lox.out().flush();
+
+        let mut line = String::new();
+        let input = match io::stdin().read_line(&mut line) {
+            Ok(0) => reader.finish(),
+            Ok(_) => reader.feed(&line),
+            Err(e) => {
+                let _ = writeln!(lox.out(), "read error: {}", e);
+                continue;
+            }
+        };
+
+        let source = match input {
+            Input::Pending => continue,
+            Input::History => {
+                let history: Vec<String> = reader.history().map(String::from).collect();
+                for (i, entry) in history.iter().enumerate() {
+                    let _ = writeln!(lox.out(), "{:>4}  {}", i + 1, entry.trim_end());
                 }
+                continue;
             }
-            Err(e) => println!("read error: {}", e),
-        }
+            Input::Exit => {
+                let _ = writeln!(lox.out(), "\n{}", "bye!!".green());
+                break;
+            }
+            Input::Run(source) => source,
+            Input::Edit => match repl::edit(&repl::editor(), reader.last().unwrap_or("")) {
+                Ok(source) => {
+                    reader.remember(&source);
+                    source
+                }
+                Err(e) => {
+                    let _ = writeln!(lox.err(), "Could not edit: {}", e);
+                    continue;
+                }
+            },
+        };
+
+        lox.run_source("<repl>", &source);
     }
 }
 
+// Prints how a subcommand is meant to be called and exits.
+fn usage(lox: &mut StdLox, command: &str) -> ! {
+    let _ = writeln!(lox.err(), "Usage: {}", command);
+    process::exit(EX_USAGE);
+}
+
 fn main() {
     let args: Vec<String> = args().collect();
-    let expr = Expression::NumberLiteral(100.00);
-    let sexpr = Expression::StringLiteral(String::from("Testing lol"));
-    println!("Expression: {}", expr);
-    println!("Expression: {}", sexpr);
-
-    let unary_expr = Expression::Unary {
-        operator: Token::new(TokenKind::Bang, String::from("!"), 20),
-        right: Box::new(Expression::Unary {
-            operator: Token::new(TokenKind::Bang, String::from("!"), 20),
-            right: Box::new(sexpr),
-        }),
-    };
-
-    println!("Expression: {}", unary_expr);
-
-    let binary_expr = Expression::Binary {
-        left: Box::new(unary_expr),
-        operator: Token::new(TokenKind::Plus, String::from("+"), 20),
-        right: Box::new(Expression::Unary {
-            operator: Token::new(TokenKind::Bang, String::from("!"), 20),
-            right: Box::new(expr),
-        }),
-    };
-
-    println!("Expression: {}", binary_expr);
-
-    if args.len() > 2 {
-        println!("Usage: lox [file]");
-    } else if args.len() == 2 {
-        println!("running file...");
-        run_file(&args[1]);
-    } else {
-        run_interpreter();
+    let mut lox = Lox::new(io::stdout(), io::stderr());
+    let config = Config::load().unwrap_or_else(|e| {
+        let _ = writeln!(lox.err(), "Ignoring config: {}", e);
+        Config::default()
+    });
+
+    let rest: Vec<&str> = args.iter().skip(2).map(String::as_str).collect();
+    match args.get(1).map(String::as_str) {
+        Some("highlight") => {
+            let (format, fname) = match rest.as_slice() {
+                [file] if !file.starts_with("--") => (config.highlight_format, *file),
+                ["--ansi", file] => (highlight::Format::Ansi, *file),
+                ["--html", file] => (highlight::Format::Html, *file),
+                _ => usage(&mut lox, "lox highlight [--html|--ansi] <file>"),
+            };
+            exit(highlight_file(&mut lox, fname, format));
+        }
+        Some("check") => {
+            if rest.is_empty() {
+                usage(&mut lox, "lox check <file>...");
+            }
+            // Every file is checked; the exit code is the worst of them.
+            let mut code = 0;
+            for fname in rest {
+                code = code.max(match read_file(&mut lox, fname) {
+                    Some(program) if lox.check(fname, &program) => 0,
+                    Some(_) => EX_DATAERR,
+                    None => EX_NOINPUT,
+                });
+            }
+            process::exit(code);
+        }
+        Some("tokens") => {
+            let (json, fname) = match rest.as_slice() {
+                [file] if !file.starts_with("--") => (false, *file),
+                ["--json", file] => (true, *file),
+                _ => usage(&mut lox, "lox tokens [--json] <file>"),
+            };
+            let program = match read_file(&mut lox, fname) {
+                Some(program) => program,
+                None => process::exit(EX_NOINPUT),
+            };
+            let clean = lox.tokens(fname, &program, json);
+            process::exit(if clean { 0 } else { EX_DATAERR });
+        }
+        Some("--time") => match rest.as_slice() {
+            [file] => exit(time_file(&mut lox, file)),
+            _ => usage(&mut lox, "lox --time <file>"),
+        },
+        Some("--watch") => match rest.as_slice() {
+            [file] => watch_file(&mut lox, file),
+            _ => usage(&mut lox, "lox --watch <file>"),
+        },
+        Some("-e") => match rest.as_slice() {
+            [source] => exit(run_program(&mut lox, "<-e>", source)),
+            _ => usage(&mut lox, "lox -e <source>"),
+        },
+        Some(file) if rest.is_empty() && !file.starts_with('-') => exit(run_file(&mut lox, file)),
+        Some(_) => {
+            let _ = writeln!(lox.err(), "Usage: lox [file]");
+            let _ = writeln!(lox.err(), "       lox -e <source>");
+            let _ = writeln!(lox.err(), "       lox --time <file>");
+            let _ = writeln!(lox.err(), "       lox --watch <file>");
+            let _ = writeln!(lox.err(), "       lox check <file>...");
+            let _ = writeln!(lox.err(), "       lox tokens [--json] <file>");
+            let _ = writeln!(lox.err(), "       lox highlight [--html|--ansi] <file>");
+            process::exit(EX_USAGE);
+        }
+        None => run_interpreter(&mut lox, &config),
     }
 }
@@ -0,0 +1,66 @@
+// Formats a number the way jlox prints it: Java's Double.toString with a
+// trailing ".0" dropped, so integral values print without a fraction.
+// Anything outside [0.001, 10^7) switches to Java's scientific notation.
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return String::from("NaN");
+    }
+    if n.is_infinite() {
+        return String::from(if n > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+
+    let magnitude = n.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        return format!("{}", n);
+    }
+
+    let scientific = format!("{:e}", n);
+    match scientific.split_once('e') {
+        Some((mantissa, exponent)) if mantissa.contains('.') => {
+            format!("{}E{}", mantissa, exponent)
+        }
+        Some((mantissa, exponent)) => format!("{}.0E{}", mantissa, exponent),
+        None => scientific,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers_have_no_fraction() {
+        assert_eq!("4", format_number(2.0 + 2.0));
+        assert_eq!("-3", format_number(-3.0));
+        assert_eq!("1234567", format_number(1234567.0));
+    }
+
+    #[test]
+    fn fractions_keep_their_digits() {
+        assert_eq!("2.75", format_number(2.75));
+        assert_eq!("0.5", format_number(1.0 / 2.0));
+        assert_eq!("0.001", format_number(0.001));
+        assert_eq!("0.30000000000000004", format_number(0.1 + 0.2));
+    }
+
+    #[test]
+    fn zero_keeps_its_sign() {
+        assert_eq!("0", format_number(0.0));
+        assert_eq!("-0", format_number(-0.0));
+    }
+
+    #[test]
+    fn large_and_small_numbers_use_scientific_notation() {
+        assert_eq!("1.0E7", format_number(1e7));
+        assert_eq!("1.0E21", format_number(1e21));
+        assert_eq!("1.5E-4", format_number(0.00015));
+        assert_eq!("-2.5E10", format_number(-2.5e10));
+    }
+
+    #[test]
+    fn non_finite_values() {
+        assert_eq!("NaN", format_number(f64::NAN));
+        assert_eq!("Infinity", format_number(f64::INFINITY));
+        assert_eq!("-Infinity", format_number(f64::NEG_INFINITY));
+    }
+}
@@ -1,186 +1,477 @@
-use crate::expression::Expression;
+use crate::expression::{Expression, ExpressionKind};
 use crate::lox_err::LoxErr;
-use crate::token::{Token, TokenKind};
+use crate::token::{Literal, Token, TokenKind};
+use crate::token_cursor::TokenCursor;
+
+// Binding power of an operator, loosest first. Ordering matters: the
+// parser keeps consuming infix operators while they bind at least as
+// tightly as the level it was asked to parse.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub(crate) enum Precedence {
+    None,
+    Equality,   // == !=
+    Comparison, // < > <= >=
+    Term,       // + -
+    Factor,     // * /
+    Unary,      // ! -
+}
+
+impl Precedence {
+    // Infix precedence table; anything that is not a binary operator ends
+    // the expression.
+    pub(crate) fn of(kind: &TokenKind) -> Precedence {
+        match kind {
+            TokenKind::BangEqual | TokenKind::EqualEqual => Precedence::Equality,
+            TokenKind::Greater
+            | TokenKind::GreaterEqual
+            | TokenKind::Less
+            | TokenKind::LessEqual => Precedence::Comparison,
+            TokenKind::Minus | TokenKind::Plus => Precedence::Term,
+            TokenKind::Slash | TokenKind::Star => Precedence::Factor,
+            _ => Precedence::None,
+        }
+    }
+
+    // The level for a binary operator's right operand; one step tighter
+    // makes every binary operator left-associative.
+    pub(crate) fn next(self) -> Precedence {
+        match self {
+            Precedence::None => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor | Precedence::Unary => Precedence::Unary,
+        }
+    }
+}
+
+// How deeply expressions may nest before parsing gives up, so hostile
+// input can't overflow the stack. Parentheses, prefix operators and each
+// binary operator in a chain all add a level: `1 + 1 + ...` builds a tree
+// as deep as it is long, and printing or dropping it recurses that far.
+const MAX_DEPTH: usize = 200;
 
 pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+    tokens: TokenCursor,
+    depth: usize,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
         Parser {
-            tokens: tokens,
-            current: 0,
+            tokens: TokenCursor::new(tokens),
+            depth: 0,
         }
     }
 
+    // Parses the whole token stream as one expression; anything left over
+    // after it is an error rather than silently dropped.
     pub fn parse(&mut self) -> Result<Expression, LoxErr> {
-        self.parse_equality()
-    }
-
-    // equality → comparison ( ( "!=" | "==" ) comparison )*
-    fn parse_equality(&mut self) -> Result<Expression, LoxErr> {
-        let mut expr = self.parse_comparison()?;
-        while self.match_tokens(&vec![TokenKind::BangEqual, TokenKind::EqualEqual]) {
-            let operator = self.previous();
-            let right = self.parse_comparison()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator: operator,
-                right: Box::new(right),
-            };
+        let expr = self.parse_precedence(Precedence::Equality)?;
+
+        if !self.tokens.is_at_end() {
+            let token = self.tokens.peek();
+            return Err(LoxErr::new(
+                token.line,
+                format!("Expected end of expression, got: {}", token.kind),
+            )
+            .with_span(token.span));
         }
         Ok(expr)
     }
 
-    // comparison → addition ( ( ">" | ">=" | "<" | "<=" ) addition )*
-    fn parse_comparison(&mut self) -> Result<Expression, LoxErr> {
-        let mut expr = self.parse_addition()?;
-        let operators = vec![
-            TokenKind::Greater,
-            TokenKind::GreaterEqual,
-            TokenKind::Less,
-            TokenKind::LessEqual,
-        ];
-
-        while self.match_tokens(&operators) {
-            let operator = self.previous();
-            let right = self.parse_addition()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator: operator,
-                right: Box::new(right),
-            };
-        }
-        Ok(expr)
+    // Parses a prefix expression, then folds in infix operators for as long
+    // as they bind at least as tightly as `precedence`.
+    fn parse_precedence(&mut self, precedence: Precedence) -> Result<Expression, LoxErr> {
+        let depth = self.depth;
+        let expr = self.parse_infix(precedence);
+        self.depth = depth;
+        expr
     }
 
-    fn parse_addition(&mut self) -> Result<Expression, LoxErr> {
-        let mut expr = self.parse_multiplication()?;
-        while self.match_tokens(&vec![TokenKind::Minus, TokenKind::Plus]) {
-            let operator = self.previous();
-            let right = self.parse_multiplication()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator: operator,
-                right: Box::new(right),
-            }
+    // Goes one level deeper, failing once MAX_DEPTH is reached.
+    fn descend(&mut self) -> Result<(), LoxErr> {
+        if self.depth == MAX_DEPTH {
+            let token = self.tokens.peek();
+            return Err(
+                LoxErr::new(token.line, String::from("Expression is nested too deeply."))
+                    .with_span(token.span),
+            );
         }
-        Ok(expr)
+        self.depth += 1;
+        Ok(())
     }
 
-    fn parse_multiplication(&mut self) -> Result<Expression, LoxErr> {
-        let mut expr = self.parse_unary()?;
-        while self.match_tokens(&vec![TokenKind::Slash, TokenKind::Star]) {
-            let operator = self.previous();
-            let right = self.parse_unary()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator: operator,
-                right: Box::new(right),
-            }
+    fn parse_infix(&mut self, precedence: Precedence) -> Result<Expression, LoxErr> {
+        self.descend()?;
+        let mut expr = self.parse_prefix()?;
+
+        while !self.tokens.is_at_end() && Precedence::of(&self.tokens.peek().kind) >= precedence {
+            // Each operator puts everything parsed so far one level down.
+            self.descend()?;
+            let operator = self.tokens.advance();
+            let right = self.parse_precedence(Precedence::of(&operator.kind).next())?;
+            let span = expr.span.merge(right.span);
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
         Ok(expr)
     }
 
-    fn parse_unary(&mut self) -> Result<Expression, LoxErr> {
-        if self.match_tokens(&vec![TokenKind::Bang, TokenKind::Minus]) {
-            let operator = self.previous();
-            let right = self.parse_unary()?;
-            Ok(Expression::Unary {
-                operator: operator,
-                right: Box::new(right),
-            })
+    fn parse_prefix(&mut self) -> Result<Expression, LoxErr> {
+        if let Some(operator) = self.tokens.eat(&[TokenKind::Bang, TokenKind::Minus]) {
+            let right = self.parse_precedence(Precedence::Unary)?;
+            let span = operator.span.merge(right.span);
+            Ok(Expression::new(
+                ExpressionKind::Unary {
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            ))
         } else {
             self.parse_primary()
         }
     }
 
     fn parse_primary(&mut self) -> Result<Expression, LoxErr> {
-        if self.match_tokens(&vec![TokenKind::True]) {
-            Ok(Expression::BoolLiteral(true))
-        } else if self.match_tokens(&vec![TokenKind::False]) {
-            Ok(Expression::BoolLiteral(false))
-        } else if self.match_tokens(&vec![TokenKind::Nil]) {
-            Ok(Expression::NilLiteral)
-        } else if self.match_tokens(&vec![TokenKind::Number]) {
-            let number_token = self.previous();
-            match number_token.lexeme.parse() {
-                Ok(v) => Ok(Expression::NumberLiteral(v)),
-                Err(_) => Err(LoxErr::new(
-                    number_token.line,
-                    format!("Could not parse number: {}", number_token.lexeme),
-                )),
+        let token = self.tokens.advance();
+        let kind = match token.kind {
+            TokenKind::True => ExpressionKind::BoolLiteral(true),
+            TokenKind::False => ExpressionKind::BoolLiteral(false),
+            TokenKind::Nil => ExpressionKind::NilLiteral,
+            TokenKind::Number | TokenKind::Str => match &token.literal {
+                Some(Literal::Number(n)) => ExpressionKind::NumberLiteral(*n),
+                Some(Literal::Str(s)) => ExpressionKind::StringLiteral(s.clone()),
+                None => {
+                    return Err(LoxErr::new(
+                        token.line,
+                        format!("Literal without a value: {}", token.lexeme),
+                    )
+                    .with_span(token.span))
+                }
+            },
+            TokenKind::LeftParen => {
+                let expr = self.parse_precedence(Precedence::Equality)?;
+                self.tokens.expect(TokenKind::RightParen)?;
+
+                ExpressionKind::Grouping(Box::new(expr))
+            }
+            _ => {
+                return Err(
+                    LoxErr::new(token.line, format!("Unknown primary: {:?}", token.lexeme))
+                        .with_span(token.span),
+                )
+            }
+        };
+
+        Ok(Expression::new(kind, self.tokens.span_from(&token)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{binary, grouping, node, number, operator, parse, unary, Rng};
+    use crate::token::Span;
+
+    const EQUALITY: usize = 1;
+    const COMPARISON: usize = 2;
+    const ADDITION: usize = 3;
+    const MULTIPLICATION: usize = 4;
+    const UNARY: usize = 5;
+
+    fn binary_operator(rng: &mut Rng, precedence: usize) -> Token {
+        let choices = match precedence {
+            EQUALITY => vec![(TokenKind::EqualEqual, "=="), (TokenKind::BangEqual, "!=")],
+            COMPARISON => vec![
+                (TokenKind::Greater, ">"),
+                (TokenKind::GreaterEqual, ">="),
+                (TokenKind::Less, "<"),
+                (TokenKind::LessEqual, "<="),
+            ],
+            ADDITION => vec![(TokenKind::Plus, "+"), (TokenKind::Minus, "-")],
+            _ => vec![(TokenKind::Star, "*"), (TokenKind::Slash, "/")],
+        };
+        let (kind, lexeme) = choices[rng.below(choices.len())].clone();
+        operator(kind, lexeme)
+    }
+
+    fn literal(rng: &mut Rng) -> Expression {
+        node(match rng.below(5) {
+            0 => ExpressionKind::NumberLiteral(rng.below(1000) as f64),
+            1 => ExpressionKind::NumberLiteral(rng.below(1000) as f64 / 8.0),
+            2 => {
+                let length = rng.below(6);
+                let text = (0..length)
+                    .map(|_| (b'a' + rng.below(26) as u8) as char)
+                    .collect();
+                ExpressionKind::StringLiteral(text)
+            }
+            3 => ExpressionKind::BoolLiteral(rng.below(2) == 0),
+            _ => ExpressionKind::NilLiteral,
+        })
+    }
+
+    // Generates a tree the parser could have produced: a child that binds
+    // looser than `min_precedence` is wrapped in a grouping.
+    fn generate(rng: &mut Rng, depth: usize, min_precedence: usize) -> Expression {
+        if depth == 0 {
+            return literal(rng);
+        }
+
+        let (expr, precedence) = match rng.below(4) {
+            0 => (literal(rng), UNARY + 1),
+            1 => {
+                let inner = generate(rng, depth - 1, EQUALITY);
+                (node(ExpressionKind::Grouping(Box::new(inner))), UNARY + 1)
+            }
+            2 => {
+                let operator = if rng.below(2) == 0 {
+                    operator(TokenKind::Bang, "!")
+                } else {
+                    operator(TokenKind::Minus, "-")
+                };
+                let right = generate(rng, depth - 1, UNARY);
+                (
+                    node(ExpressionKind::Unary {
+                        operator,
+                        right: Box::new(right),
+                    }),
+                    UNARY,
+                )
             }
-        } else if self.match_tokens(&vec![TokenKind::Str]) {
-            Ok(Expression::StringLiteral(self.previous().lexeme))
-        } else if self.match_tokens(&vec![TokenKind::LeftParen]) {
-            let expr = self.parse_comparison()?;
-            self.consume(TokenKind::RightParen)?;
+            _ => {
+                let precedence = EQUALITY + rng.below(MULTIPLICATION - EQUALITY + 1);
+                let left = generate(rng, depth - 1, precedence);
+                let operator = binary_operator(rng, precedence);
+                let right = generate(rng, depth - 1, precedence + 1);
+                (
+                    node(ExpressionKind::Binary {
+                        left: Box::new(left),
+                        operator,
+                        right: Box::new(right),
+                    }),
+                    precedence,
+                )
+            }
+        };
 
-            Ok(Expression::Grouping(Box::new(expr)))
+        if precedence < min_precedence {
+            node(ExpressionKind::Grouping(Box::new(expr)))
         } else {
-            let token = self.peek();
-            Err(LoxErr::new(
-                token.line,
-                format!("Unknown primary: {:?}", token.lexeme),
-            ))
+            expr
         }
     }
 
-    fn match_tokens(&mut self, token_kinds: &Vec<TokenKind>) -> bool {
-        for kind in token_kinds {
-            if self.check(kind) {
-                self.advance();
-                return true;
+    #[test]
+    fn print_then_parse_round_trips() {
+        let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..500 {
+            let expr = generate(&mut rng, 5, EQUALITY);
+            let source = format!("{:#}", expr);
+
+            match parse(&source) {
+                Ok(parsed) => assert_eq!(expr, parsed, "source: {}", source),
+                Err(err) => panic!("failed to parse {}: {}", source, err),
             }
         }
-        false
     }
 
-    fn check(&self, kind: &TokenKind) -> bool {
-        if self.is_at_end() {
-            false
-        } else {
-            self.peek().kind == *kind
-        }
+    #[test]
+    fn parses_literals() {
+        assert_eq!(number(12.5), parse("12.5").unwrap());
+        assert_eq!(
+            node(ExpressionKind::StringLiteral(String::from("hi"))),
+            parse("\"hi\"").unwrap()
+        );
+        assert_eq!(
+            node(ExpressionKind::BoolLiteral(true)),
+            parse("true").unwrap()
+        );
+        assert_eq!(
+            node(ExpressionKind::BoolLiteral(false)),
+            parse("false").unwrap()
+        );
+        assert_eq!(node(ExpressionKind::NilLiteral), parse("nil").unwrap());
     }
 
-    fn is_at_end(&self) -> bool {
-        self.peek().kind == TokenKind::Eof
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let deep = format!("{}1{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert!(parse(&deep).is_err());
+
+        let shallow = format!("{}1{}", "(".repeat(50), ")".repeat(50));
+        assert!(parse(&shallow).is_ok());
+
+        let err = parse(&"-".repeat(10_000)).unwrap_err();
+        assert!(format!("{}", err).contains("nested too deeply"));
+
+        let err = parse(&format!("{}1", "1+".repeat(200_000))).unwrap_err();
+        assert!(format!("{}", err).contains("nested too deeply"));
+
+        let long = format!("{}1", "1+".repeat(MAX_DEPTH / 2));
+        assert!(parse(&long).is_ok());
     }
 
-    fn peek(&self) -> Token {
-        self.tokens[self.current].clone()
+    #[test]
+    fn literal_values_come_from_the_token() {
+        // The lexeme is never read, only the value the scanner attached.
+        let tokens = vec![
+            Token::new(TokenKind::Number, String::from("1"), 1, Span::default())
+                .with_literal(Literal::Number(2.0)),
+            Token::new(TokenKind::Eof, String::new(), 1, Span::default()),
+        ];
+        assert_eq!(number(2.0), Parser::new(tokens).parse().unwrap());
+
+        let tokens = vec![
+            Token::new(TokenKind::Number, String::from("1"), 1, Span::default()),
+            Token::new(TokenKind::Eof, String::new(), 1, Span::default()),
+        ];
+        assert!(Parser::new(tokens).parse().is_err());
     }
 
-    fn previous(&self) -> Token {
-        self.tokens[self.current - 1].clone()
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        assert_eq!(
+            binary(
+                number(1.0),
+                TokenKind::Plus,
+                "+",
+                binary(number(2.0), TokenKind::Star, "*", number(3.0))
+            ),
+            parse("1 + 2 * 3").unwrap()
+        );
     }
 
-    fn advance(&mut self) -> Token {
-        if !self.is_at_end() {
-            self.current += 1;
-        }
+    #[test]
+    fn binary_operators_are_left_associative() {
+        assert_eq!(
+            binary(
+                binary(number(1.0), TokenKind::Minus, "-", number(2.0)),
+                TokenKind::Minus,
+                "-",
+                number(3.0)
+            ),
+            parse("1 - 2 - 3").unwrap()
+        );
+    }
 
-        self.previous()
+    #[test]
+    fn comparison_binds_tighter_than_equality() {
+        assert_eq!(
+            binary(
+                binary(number(1.0), TokenKind::Less, "<", number(2.0)),
+                TokenKind::EqualEqual,
+                "==",
+                node(ExpressionKind::BoolLiteral(true))
+            ),
+            parse("1 < 2 == true").unwrap()
+        );
     }
 
-    fn consume(&mut self, kind: TokenKind) -> Result<(), LoxErr> {
-        let expected = vec![kind];
-        if !self.match_tokens(&expected) {
-            let token = self.peek();
-            Err(LoxErr::new(
-                token.line,
-                format!(
-                    "Unexpected token. expected: {:?}, got: {:?}",
-                    expected.first(),
-                    token.kind
-                )
-            ))
-        } else {
-            Ok(())
+    #[test]
+    fn unary_operators_nest() {
+        assert_eq!(
+            unary(
+                TokenKind::Bang,
+                "!",
+                unary(TokenKind::Minus, "-", number(4.0))
+            ),
+            parse("!-4").unwrap()
+        );
+    }
+
+    #[test]
+    fn grouping_overrides_precedence() {
+        assert_eq!(
+            binary(
+                grouping(binary(number(1.0), TokenKind::Plus, "+", number(2.0))),
+                TokenKind::Star,
+                "*",
+                number(3.0)
+            ),
+            parse("(1 + 2) * 3").unwrap()
+        );
+    }
+
+    #[test]
+    fn grouping_allows_equality() {
+        assert_eq!(
+            grouping(binary(
+                number(1.0),
+                TokenKind::EqualEqual,
+                "==",
+                number(2.0)
+            )),
+            parse("(1 == 2)").unwrap()
+        );
+    }
+
+    #[test]
+    fn unary_binds_tighter_than_factor() {
+        assert_eq!(
+            binary(
+                unary(TokenKind::Minus, "-", number(2.0)),
+                TokenKind::Star,
+                "*",
+                number(3.0)
+            ),
+            parse("-2 * 3").unwrap()
+        );
+    }
+
+    #[test]
+    fn precedence_table_orders_levels() {
+        assert!(Precedence::of(&TokenKind::Star) > Precedence::of(&TokenKind::Plus));
+        assert!(Precedence::of(&TokenKind::Plus) > Precedence::of(&TokenKind::Less));
+        assert!(Precedence::of(&TokenKind::Less) > Precedence::of(&TokenKind::EqualEqual));
+        assert_eq!(Precedence::None, Precedence::of(&TokenKind::RightParen));
+    }
+
+    #[test]
+    fn nodes_carry_source_spans() {
+        let expr = parse("(1 + 2) * -3").unwrap();
+
+        assert_eq!(Span::new(0, 12), expr.span);
+        match &expr.kind {
+            ExpressionKind::Binary { left, right, .. } => {
+                assert_eq!(Span::new(0, 7), left.span);
+                assert_eq!(Span::new(10, 12), right.span);
+                match &left.kind {
+                    ExpressionKind::Grouping(inner) => assert_eq!(Span::new(1, 6), inner.span),
+                    kind => panic!("expected a grouping, got {:?}", kind),
+                }
+            }
+            kind => panic!("expected a binary expression, got {:?}", kind),
         }
     }
+
+    #[test]
+    fn unclosed_grouping_is_an_error() {
+        assert!(parse("(1 + 2").is_err());
+    }
+
+    #[test]
+    fn missing_operand_is_an_error() {
+        assert!(parse("1 +").is_err());
+    }
+
+    #[test]
+    fn trailing_tokens_are_an_error() {
+        let err = parse("1 2 )").unwrap_err();
+        assert!(err
+            .message()
+            .contains("Expected end of expression, got: NUMBER"));
+        assert_eq!(Some(Span::new(2, 3)), err.span());
+
+        assert!(parse("(1) )").is_err());
+    }
 }
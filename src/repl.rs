@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// What terminals wrap pasted text in once bracketed paste is switched on
+// (by writing ENABLE_BRACKETED_PASTE), so a multi-line paste arrives as one
+// input instead of being run line by line.
+const ENABLE_BRACKETED_PASTE: &str = "\x1b[?2004h";
+const DISABLE_BRACKETED_PASTE: &str = "\x1b[?2004l";
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    // A complete piece of source to run.
+    Run(String),
+    // More lines are needed before there is anything to run.
+    Pending,
+    // Open the last input in an editor and run what is saved.
+    Edit,
+    // List the inputs run so far.
+    History,
+    Exit,
+}
+
+// Turns the lines typed at the REPL into inputs. Most lines stand alone,
+// but `:paste` (ended by `:end` or end of input) and bracketed pastes
+// collect several lines into one. The last `history_size` inputs are kept
+// for `:history`.
+pub struct LineReader {
+    paste: Option<String>,
+    history: VecDeque<String>,
+    history_size: usize,
+}
+
+impl LineReader {
+    pub fn new(history_size: usize) -> LineReader {
+        LineReader {
+            paste: None,
+            history: VecDeque::new(),
+            history_size,
+        }
+    }
+
+    pub fn in_paste(&self) -> bool {
+        self.paste.is_some()
+    }
+
+    // The last input that was run, for `:edit`.
+    pub fn last(&self) -> Option<&str> {
+        self.history.back().map(|s| s.as_str())
+    }
+
+    // Past inputs, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &str> {
+        self.history.iter().map(|s| s.as_str())
+    }
+
+    pub fn feed(&mut self, line: &str) -> Input {
+        let line = line.trim_end_matches(&['\n', '\r'][..]);
+
+        if let Some(mut buffer) = self.paste.take() {
+            if line.trim_end() == ":end" {
+                return self.run(buffer);
+            }
+            if let Some((pasted, typed)) = line.split_once(PASTE_END) {
+                buffer.push_str(pasted);
+                buffer.push_str(typed);
+                return self.run(buffer);
+            }
+            buffer.push_str(line);
+            buffer.push('\n');
+            self.paste = Some(buffer);
+            return Input::Pending;
+        }
+
+        // A paste can start after something was typed on the line, and more
+        // can be typed after it ends; all of it is one input.
+        if let Some((typed, pasted)) = line.split_once(PASTE_START) {
+            self.paste = Some(String::from(typed));
+            return self.feed(pasted);
+        }
+
+        match line.trim_end() {
+            // Nothing to run, and nothing worth keeping for :edit.
+            "" => Input::Pending,
+            ":paste" => {
+                self.paste = Some(String::new());
+                Input::Pending
+            }
+            ":edit" => Input::Edit,
+            ":history" => Input::History,
+            "exit" => Input::Exit,
+            line => self.run(String::from(line)),
+        }
+    }
+
+    // Called at the end of input: runs a paste still being collected,
+    // otherwise there is nothing left to do.
+    pub fn finish(&mut self) -> Input {
+        match self.paste.take() {
+            Some(buffer) => self.run(buffer),
+            None => Input::Exit,
+        }
+    }
+
+    // Records source that came from somewhere other than `feed` (the
+    // editor) as the last input.
+    pub fn remember(&mut self, source: &str) {
+        self.history.push_back(String::from(source));
+        // Always keep the last input, which `:edit` needs.
+        while self.history.len() > self.history_size.max(1) {
+            self.history.pop_front();
+        }
+    }
+
+    fn run(&mut self, source: String) -> Input {
+        self.remember(&source);
+        Input::Run(source)
+    }
+}
+
+// The editor to use for `:edit`: $VISUAL, then $EDITOR, then vi.
+pub fn editor() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"))
+}
+
+// Opens `text` in `editor` (which may include arguments, e.g. `code -w`)
+// and returns what was saved once the editor exits.
+pub fn edit(editor: &str, text: &str) -> io::Result<String> {
+    let (path, mut file) = temp_file()?;
+    let written = file.write_all(text.as_bytes());
+    drop(file);
+
+    let edited = written.and_then(|_| run_editor(editor, &path));
+    let removed = fs::remove_file(&path);
+    let edited = edited?;
+    removed?;
+    Ok(edited)
+}
+
+fn run_editor(editor: &str, path: &Path) -> io::Result<String> {
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no editor set"))?;
+    let status = Command::new(program).args(words).arg(path).status()?;
+
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}",
+            program, status
+        )));
+    }
+    fs::read_to_string(path)
+}
+
+// Creates a new file in the temp directory, readable only by this user.
+// The file must not exist yet: /tmp is shared, and following a link someone
+// else left at the same name would overwrite whatever it points to.
+fn temp_file() -> io::Result<(PathBuf, File)> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut attempts = 0;
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or_default();
+        let path = env::temp_dir().join(format!("lox-edit-{}-{}.lox", process::id(), nanos));
+
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < 10 => attempts += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Switches bracketed paste on for as long as it is alive, so the terminal
+// is put back however the REPL ends, a panic included.
+pub struct BracketedPaste;
+
+impl BracketedPaste {
+    pub fn enable() -> BracketedPaste {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "{}", ENABLE_BRACKETED_PASTE);
+        let _ = stdout.flush();
+        BracketedPaste
+    }
+}
+
+impl Drop for BracketedPaste {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "{}", DISABLE_BRACKETED_PASTE);
+        let _ = stdout.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_lines_run_straight_away() {
+        let mut reader = LineReader::new(10);
+
+        assert_eq!(Input::Run(String::from("1 + 2")), reader.feed("1 + 2\n"));
+        assert_eq!(Some("1 + 2"), reader.last());
+        assert_eq!(Input::Exit, reader.feed("exit\n"));
+        assert_eq!(Input::Edit, reader.feed(":edit\n"));
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let mut reader = LineReader::new(10);
+        reader.feed("1 + 2\n");
+
+        assert_eq!(Input::Pending, reader.feed("\n"));
+        assert_eq!(Input::Pending, reader.feed(" \t\r\n"));
+        assert_eq!(Some("1 + 2"), reader.last());
+        assert_eq!(1, reader.history().count());
+    }
+
+    #[test]
+    fn history_keeps_the_latest_inputs() {
+        let mut reader = LineReader::new(2);
+        reader.feed("1\n");
+        reader.feed("2\n");
+        reader.feed("3\n");
+
+        assert_eq!(Input::History, reader.feed(":history\n"));
+        assert_eq!(vec!["2", "3"], reader.history().collect::<Vec<_>>());
+        assert_eq!(Some("3"), reader.last());
+    }
+
+    #[test]
+    fn paste_mode_collects_until_end() {
+        let mut reader = LineReader::new(10);
+
+        assert_eq!(Input::Pending, reader.feed(":paste\n"));
+        assert!(reader.in_paste());
+        assert_eq!(Input::Pending, reader.feed("1 +\n"));
+        assert_eq!(Input::Pending, reader.feed("exit\n"));
+        assert_eq!(
+            Input::Run(String::from("1 +\nexit\n")),
+            reader.feed(":end\n")
+        );
+        assert!(!reader.in_paste());
+    }
+
+    #[test]
+    fn end_of_input_finishes_a_paste() {
+        let mut reader = LineReader::new(10);
+        reader.feed(":paste\n");
+        reader.feed("(1)\n");
+
+        assert_eq!(Input::Run(String::from("(1)\n")), reader.finish());
+        assert_eq!(Input::Exit, reader.finish());
+    }
+
+    #[test]
+    fn bracketed_pastes_are_one_input() {
+        let mut reader = LineReader::new(10);
+
+        assert_eq!(Input::Pending, reader.feed("\x1b[200~1 +\n"));
+        assert_eq!(Input::Pending, reader.feed("2\n"));
+        assert_eq!(
+            Input::Run(String::from("1 +\n2\n3")),
+            reader.feed("3\x1b[201~\n")
+        );
+        assert_eq!(
+            Input::Run(String::from("nil")),
+            reader.feed("\x1b[200~nil\x1b[201~\n")
+        );
+    }
+
+    #[test]
+    fn bracketed_pastes_keep_typed_text_around_them() {
+        let mut reader = LineReader::new(10);
+
+        assert_eq!(
+            Input::Run(String::from("1 + 2 * 3")),
+            reader.feed("1 + \x1b[200~2\x1b[201~ * 3\n")
+        );
+        assert_eq!(Input::Pending, reader.feed("-\x1b[200~(1 +\n"));
+        assert_eq!(
+            Input::Run(String::from("-(1 +\n2) == nil")),
+            reader.feed("2)\x1b[201~ == nil\n")
+        );
+    }
+
+    #[test]
+    fn temp_files_are_new_and_private() {
+        let (first, _) = temp_file().unwrap();
+        let (second, _) = temp_file().unwrap();
+        assert_ne!(first, second);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&first).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+        fs::remove_file(first).unwrap();
+        fs::remove_file(second).unwrap();
+    }
+
+    #[test]
+    fn edit_returns_the_saved_text() {
+        // `true` leaves the file alone, as if it was saved unchanged.
+        assert_eq!("1 + 2\n", edit("true", "1 + 2\n").unwrap());
+        assert!(edit("false", "1").is_err());
+        assert!(edit("", "1").is_err());
+    }
+}
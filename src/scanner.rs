@@ -1,5 +1,5 @@
 use crate::lox_err::LoxErr;
-use crate::token::{Token, TokenKind};
+use crate::token::{Literal, Span, Token, TokenKind};
 use colored::*;
 
 #[derive(Debug)]
@@ -9,6 +9,8 @@ pub struct Scanner {
     start: usize,
     current: usize,
     line: usize,
+    emit_trivia: bool,
+    ignore_keyword_case: bool,
 }
 
 impl Scanner {
@@ -19,28 +21,73 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            emit_trivia: false,
+            ignore_keyword_case: false,
         }
     }
 
+    // Like `new`, but whitespace and comments are kept in the token stream
+    // as Whitespace/Comment tokens, so the tokens cover the whole source.
+    pub fn with_trivia(source: String) -> Scanner {
+        Scanner {
+            emit_trivia: true,
+            ..Scanner::new(source)
+        }
+    }
+
+    // Treats keywords case-insensitively, so `Print` scans as `print`. Meant
+    // for teaching setups where the exact spelling is a distraction.
+    pub fn ignore_keyword_case(mut self) -> Scanner {
+        self.ignore_keyword_case = true;
+        self
+    }
+
+    // Picks up scanning `source` at character `offset` on `line`, for
+    // re-scanning part of a file. `offset` must be somewhere a previous scan
+    // of the same text finished a token (or the start of the source).
+    pub fn resume(source: String, offset: usize, line: usize, emit_trivia: bool) -> Scanner {
+        Scanner {
+            start: offset,
+            current: offset,
+            line,
+            emit_trivia,
+            ..Scanner::new(source)
+        }
+    }
+
+    // Consumes the next character. At the end of the source there is
+    // nothing to consume, and like peek_token this returns '\0'.
     fn advance(&mut self) -> char {
-        self.current += 1;
-        self.source[self.current - 1]
+        let c = self.peek_token();
+        if !self.at_end() {
+            self.current += 1;
+        }
+        c
     }
 
     fn push_token(&mut self, kind: TokenKind, lexeme: Option<String>) {
+        let token = self.make_token(kind, lexeme);
+        self.tokens.push(token);
+    }
+
+    fn make_token(&self, kind: TokenKind, lexeme: Option<String>) -> Token {
         let lexeme = match lexeme {
             Some(l) => l,
             None => self.token_literal(),
         };
-        self.tokens.push(Token::new(kind, lexeme, self.line));
+        let span = Span::new(self.start, self.current);
+        Token::new(kind, lexeme, self.line, span)
     }
 
     fn token_literal(&self) -> String {
+        self.text(self.start, self.current)
+    }
+
+    fn text(&self, start: usize, end: usize) -> String {
         self.source
-            .get(self.start..self.current)
-            .unwrap()
-            .into_iter()
-            .collect()
+            .get(start..end)
+            .map(|chars| chars.iter().collect())
+            .unwrap_or_default()
     }
 
     fn scan_token(&mut self) -> Result<(), LoxErr> {
@@ -84,31 +131,30 @@ impl Scanner {
                 }
                 _ => self.push_token(TokenKind::Greater, None),
             },
-            '/' => match self.peek_token() {
-                '/' => {
-                    self.peek_until('\n');
-                }
-                _ => self.push_token(TokenKind::Slash, None),
-            },
+            '/' => self.push_token(TokenKind::Slash, None),
             '"' => {
-                self.peek_until('"');
+                while !self.at_end() && self.peek_token() != '"' {
+                    if self.advance() == '\n' {
+                        self.line += 1;
+                    }
+                }
 
                 if self.at_end() {
+                    self.push_token(TokenKind::Error, None);
                     return Err(LoxErr::new(
                         self.line,
                         format!("Unterminated string: '{}'", self.token_literal().bold()),
-                    ));
+                    )
+                    .with_span(Span::new(self.start, self.current)));
                 }
 
                 self.advance(); // catch closing "
 
-                let lexeme = self.token_literal();
-                self.push_token(
-                    TokenKind::Str,
-                    Some(lexeme[1..lexeme.len() - 1].to_string()),
-                );
+                // The value is everything between the quotes.
+                let value = self.text(self.start + 1, self.current - 1);
+                let token = self.make_token(TokenKind::Str, Some(value.clone()));
+                self.tokens.push(token.with_literal(Literal::Str(value)));
             }
-            ' ' | '\r' | '\t' => {} // do nothing
             ('0'..='9') => {
                 while !self.at_end() && self.is_digit(&self.peek_token()) {
                     self.advance();
@@ -121,96 +167,207 @@ impl Scanner {
                     }
                 }
 
-                self.push_token(TokenKind::Number, None);
+                // Only digits with at most one '.' between them get this
+                // far, so this can't fail, but an error beats a panic. Too
+                // many digits don't fail either: they parse to infinity.
+                let token = self.make_token(TokenKind::Number, None);
+                match token.lexeme.parse() {
+                    Ok(value) => self.tokens.push(token.with_literal(Literal::Number(value))),
+                    Err(_) => {
+                        self.push_token(TokenKind::Error, None);
+                        return Err(LoxErr::new(
+                            self.line,
+                            format!("Invalid number: '{}'", token.lexeme),
+                        )
+                        .with_span(token.span));
+                    }
+                }
             }
             ('a'..='z') | ('A'..='Z') | '_' => {
                 while self.is_alpha_numeric(&self.peek_token()) {
                     self.advance();
                 }
 
-                match TokenKind::reserve_kind(&self.token_literal()) {
+                let keyword = if self.ignore_keyword_case {
+                    TokenKind::reserve_kind_ignoring_case(&self.token_literal())
+                } else {
+                    TokenKind::reserve_kind(&self.token_literal())
+                };
+                match keyword {
                     Some(kind) => self.push_token(kind, None),
                     None => self.push_token(TokenKind::Identifier, None),
                 }
             }
-            '\n' => self.line += 1,
             _ => {
-                return Err(LoxErr::new(
-                    self.line,
-                    format!("Unexpected token: '{}'", self.token_literal().bold()),
-                ))
+                // Report a run of bad characters once, as a single Error
+                // token, and pick up again where a real token could start.
+                while !self.at_end() && !self.can_start_token(&self.peek_token()) {
+                    self.advance();
+                }
+                self.push_token(TokenKind::Error, None);
+
+                let literal = self.token_literal();
+                let mut message = format!("Unexpected token: '{}'", literal.bold());
+                if let Some(keyword) = suggest_keyword(&literal) {
+                    message.push_str(&format!(". Did you mean '{}'?", keyword.bold()));
+                }
+                return Err(
+                    LoxErr::new(self.line, message).with_span(Span::new(self.start, self.current))
+                );
             }
         };
         Ok(())
     }
 
-    fn peek_until(&mut self, expected: char) {
-        while !self.at_end() && self.peek_token() != expected {
+    fn can_start_token(&self, c: &char) -> bool {
+        match c {
+            '(' | ')' | '{' | '}' | ',' | '.' | '-' | '+' | ';' | '*' | '!' | '=' | '<' | '>'
+            | '/' | '"' => true,
+            ' ' | '\r' | '\t' | '\n' => true,
+            c => self.is_alpha_numeric(c),
+        }
+    }
+
+    // Consumes whitespace and `//` comments up to the start of the next
+    // token, counting every newline passed over.
+    fn skip_trivia(&mut self) {
+        while !self.at_end() {
+            self.start = self.current;
+            let line = self.line;
+
+            match self.peek_token() {
+                ' ' | '\r' | '\t' | '\n' => {
+                    while !self.at_end() && matches!(self.peek_token(), ' ' | '\r' | '\t' | '\n') {
+                        if self.advance() == '\n' {
+                            self.line += 1;
+                        }
+                    }
+                    self.push_trivia(TokenKind::Whitespace, line);
+                }
+                '/' if self.peek_next_token() == '/' => {
+                    self.skip_line();
+                    self.push_trivia(TokenKind::Comment, line);
+                }
+                // A `#!` line at the very start lets scripts be executable.
+                '#' if self.current == 0 && self.peek_next_token() == '!' => {
+                    self.skip_line();
+                    self.push_trivia(TokenKind::Comment, line);
+                }
+                // Editors on Windows like to start UTF-8 files with a byte
+                // order mark; it is not part of the program.
+                '\u{feff}' if self.current == 0 => {
+                    self.advance();
+                    self.push_trivia(TokenKind::Whitespace, line);
+                }
+                _ => return,
+            }
+        }
+    }
+
+    // Trivia tokens report the line they start on.
+    fn push_trivia(&mut self, kind: TokenKind, line: usize) {
+        if self.emit_trivia {
+            let span = Span::new(self.start, self.current);
+            self.tokens
+                .push(Token::new(kind, self.token_literal(), line, span));
+        }
+    }
+
+    // Consumes the rest of the line, stopping before its line ending so a
+    // `\r\n` is left out of the comment just like a bare `\n`.
+    fn skip_line(&mut self) {
+        while !self.at_end()
+            && self.peek_token() != '\n'
+            && !(self.peek_token() == '\r' && self.peek_next_token() == '\n')
+        {
             self.advance();
         }
     }
 
     fn peek_token(&self) -> char {
-        if self.at_end() {
-            '\0'
-        } else {
-            self.source[self.current]
-        }
+        self.source.get(self.current).copied().unwrap_or('\0')
     }
 
     fn is_alpha_numeric(&self, c: &char) -> bool {
-        ('a'..='z').contains(c) || ('A'..='Z').contains(c) || *c == '_' || self.is_digit(c)
+        c.is_ascii_alphabetic() || *c == '_' || self.is_digit(c)
     }
 
     fn is_digit(&self, c: &char) -> bool {
-        ('0'..='9').contains(c)
+        c.is_ascii_digit()
     }
 
     fn peek_next_token(&self) -> char {
-        if self.source.len() <= self.current + 1 {
-            '\0'
-        } else {
-            self.source[self.current + 1]
-        }
+        self.source.get(self.current + 1).copied().unwrap_or('\0')
     }
 
+    // Scans the whole source. Bad input is kept as Error tokens, so even
+    // when errors are returned tokens() holds a stream covering all of it
+    // (including trivia, for a scanner built with `with_trivia`).
     pub fn scan(&mut self) -> Result<&Vec<Token>, Vec<LoxErr>> {
         let mut errors: Vec<LoxErr> = vec![];
 
-        while !self.at_end() {
-            self.start = self.current;
-            match self.scan_token() {
-                Err(e) => errors.push(e),
-                _ => continue,
+        while let Some(result) = self.step() {
+            if let Err(e) = result {
+                errors.push(e);
             }
         }
-        self.push_token(TokenKind::Eof, Some(String::from("")));
 
-        if errors.len() == 0 {
+        if errors.is_empty() {
             Ok(&self.tokens)
         } else {
             Err(errors)
         }
     }
 
+    // Scans any trivia and then a single token. Once the input is used up
+    // the Eof token is pushed and every further call returns None.
+    pub fn step(&mut self) -> Option<Result<(), LoxErr>> {
+        self.skip_trivia();
+
+        if self.at_end() {
+            if self.tokens.last().map(|t| &t.kind) != Some(&TokenKind::Eof) {
+                self.start = self.current;
+                self.push_token(TokenKind::Eof, Some(String::from("")));
+            }
+            return None;
+        }
+
+        self.start = self.current;
+        Some(self.scan_token())
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
     fn at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 }
 
+// Lox spells the logical operators out; point people coming from C-like
+// languages at the keyword they were reaching for.
+fn suggest_keyword(literal: &str) -> Option<&'static str> {
+    match literal {
+        "&" | "&&" => Some("and"),
+        "|" | "||" => Some("or"),
+        _ => None,
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::Rng;
 
     macro_rules! assert_changes {
-      ($test:expr, from: $from:expr, to: $to:expr, $changes:block) => {
-          assert_eq!($from, $test);
+        ($test:expr, from: $from:expr, to: $to:expr, $changes:block) => {
+            assert_eq!($from, $test);
 
-          $changes;
+            $changes;
 
-          assert_eq!($to, $test);
-      }
+            assert_eq!($to, $test);
+        };
     }
 
     #[test]
@@ -256,7 +413,7 @@ mod tests {
         assert_eq!(1, scanner.tokens.len());
         let token = scanner.tokens.first().unwrap();
 
-        assert_eq!("test", token.lexeme); 
+        assert_eq!("test", token.lexeme);
     }
 
     #[test]
@@ -272,7 +429,268 @@ mod tests {
         assert_eq!(1, scanner.tokens.len());
         let token = scanner.tokens.first().unwrap();
 
-        assert_eq!(String::from("My lexeme"), token.lexeme); 
+        assert_eq!(String::from("My lexeme"), token.lexeme);
+    }
+
+    #[test]
+    fn skip_trivia_counts_lines_through_comments() {
+        let mut scanner = Scanner::new(String::from("  // one\n\t// two\r\n\n1"));
+        scanner.skip_trivia();
+
+        assert_eq!(4, scanner.line);
+        assert_eq!('1', scanner.peek_token());
+    }
+
+    #[test]
+    fn comments_end_before_crlf() {
+        let mut scanner = Scanner::with_trivia(String::from("// one\r\n2"));
+        let tokens = scanner.scan().unwrap();
+
+        assert_eq!("// one", tokens[0].lexeme);
+        assert_eq!("\r\n", tokens[1].lexeme);
+        assert_eq!(2, tokens[2].line);
+    }
+
+    #[test]
+    fn leading_byte_order_mark_is_trivia() {
+        let mut scanner = Scanner::with_trivia(String::from("\u{feff}nil"));
+        let tokens = scanner.scan().unwrap();
+
+        assert_eq!(TokenKind::Whitespace, tokens[0].kind);
+        assert_eq!(Span::new(0, 1), tokens[0].span);
+        assert_eq!(TokenKind::Nil, tokens[1].kind);
+
+        let mut scanner = Scanner::new(String::from("nil\u{feff}"));
+        assert!(scanner.scan().is_err());
+    }
+
+    #[test]
+    fn skip_trivia_stops_before_slash() {
+        let mut scanner = Scanner::new(String::from(" / 2"));
+        scanner.skip_trivia();
+
+        assert_eq!('/', scanner.peek_token());
+    }
+
+    #[test]
+    fn trivia_is_dropped_by_default() {
+        let mut scanner = Scanner::new(String::from("1 // one\n2"));
+        let kinds: Vec<TokenKind> = scanner
+            .scan()
+            .unwrap()
+            .iter()
+            .map(|t| t.kind.clone())
+            .collect();
+
+        assert_eq!(
+            vec![TokenKind::Number, TokenKind::Number, TokenKind::Eof],
+            kinds
+        );
+    }
+
+    #[test]
+    fn with_trivia_keeps_whitespace_and_comments() {
+        let mut scanner = Scanner::with_trivia(String::from("1 // one\n 2"));
+        let tokens = scanner.scan().unwrap();
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind.clone()).collect();
+
+        assert_eq!(
+            vec![
+                TokenKind::Number,
+                TokenKind::Whitespace,
+                TokenKind::Comment,
+                TokenKind::Whitespace,
+                TokenKind::Number,
+                TokenKind::Eof
+            ],
+            kinds
+        );
+        assert_eq!("// one", tokens[2].lexeme);
+        assert_eq!(1, tokens[2].line);
+        assert_eq!("\n ", tokens[3].lexeme);
+        assert_eq!(1, tokens[3].line);
+        assert_eq!(2, tokens[4].line);
+    }
+
+    #[test]
+    fn shebang_line_is_skipped() {
+        let mut scanner = Scanner::new(String::from("#!/usr/bin/env lox\n1"));
+        let tokens = scanner.scan().unwrap();
+
+        assert_eq!(TokenKind::Number, tokens[0].kind);
+        assert_eq!(2, tokens[0].line);
+    }
+
+    #[test]
+    fn shebang_only_counts_on_the_first_line() {
+        let mut scanner = Scanner::new(String::from("1\n#!/usr/bin/env lox"));
+
+        assert!(scanner.scan().is_err());
+    }
+
+    #[test]
+    fn comment_at_end_of_input() {
+        let mut scanner = Scanner::new(String::from("1 // no newline"));
+        let tokens = scanner.scan().unwrap();
+
+        assert_eq!(2, tokens.len());
+        assert_eq!(TokenKind::Eof, tokens[1].kind);
+        assert_eq!(1, tokens[1].line);
+    }
+
+    #[test]
+    fn multiline_strings_advance_the_line() {
+        let mut scanner = Scanner::new(String::from("\"a\nb\"\n1"));
+        let tokens = scanner.scan().unwrap();
+
+        assert_eq!("a\nb", tokens[0].lexeme);
+        assert_eq!(3, tokens[1].line);
+    }
+
+    #[test]
+    fn step_scans_one_token_at_a_time() {
+        let mut scanner = Scanner::new(String::from("1 + 2"));
+
+        assert!(scanner.step().unwrap().is_ok());
+        assert_eq!(1, scanner.tokens.len());
+        assert!(scanner.step().unwrap().is_ok());
+        assert!(scanner.step().unwrap().is_ok());
+        assert!(scanner.step().is_none());
+        assert!(scanner.step().is_none());
+        assert_eq!(4, scanner.tokens.len());
+        assert_eq!(TokenKind::Eof, scanner.tokens[3].kind);
+    }
+
+    #[test]
+    fn resume_starts_mid_source() {
+        let mut scanner = Scanner::resume(String::from("1 +\n2"), 3, 1, false);
+        let tokens = scanner.scan().unwrap();
+
+        assert_eq!(2, tokens.len());
+        assert_eq!("2", tokens[0].lexeme);
+        assert_eq!(2, tokens[0].line);
+        assert_eq!(Span::new(4, 5), tokens[0].span);
+    }
+
+    #[test]
+    fn unexpected_characters_become_one_error_token() {
+        let mut scanner = Scanner::new(String::from("1 $@# + 2"));
+        let errors = scanner.scan().unwrap_err();
+        let kinds: Vec<TokenKind> = scanner.tokens().iter().map(|t| t.kind.clone()).collect();
+
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            vec![
+                TokenKind::Number,
+                TokenKind::Error,
+                TokenKind::Plus,
+                TokenKind::Number,
+                TokenKind::Eof
+            ],
+            kinds
+        );
+        assert_eq!("$@#", scanner.tokens()[1].lexeme);
+        assert_eq!(Span::new(2, 5), scanner.tokens()[1].span);
+    }
+
+    #[test]
+    fn error_token_stops_at_adjacent_token() {
+        let mut scanner = Scanner::new(String::from("$$a"));
+        let _ = scanner.scan();
+
+        assert_eq!("$$", scanner.tokens()[0].lexeme);
+        assert_eq!("a", scanner.tokens()[1].lexeme);
+    }
+
+    #[test]
+    fn c_style_logical_operators_suggest_keywords() {
+        let mut scanner = Scanner::new(String::from("a && b | c $ d"));
+        let errors: Vec<String> = scanner
+            .scan()
+            .unwrap_err()
+            .iter()
+            .map(|err| err.to_string())
+            .collect();
+
+        assert_eq!(3, errors.len());
+        assert!(errors[0].contains("Did you mean"));
+        assert!(errors[1].contains("Did you mean"));
+        assert!(!errors[2].contains("Did you mean"));
+
+        assert_eq!(Some("and"), suggest_keyword("&&"));
+        assert_eq!(Some("or"), suggest_keyword("|"));
+        assert_eq!(None, suggest_keyword("&|"));
+    }
+
+    #[test]
+    fn literals_carry_their_values() {
+        let mut scanner = Scanner::new(String::from("12.5 \"hi\" x"));
+        let tokens = scanner.scan().unwrap();
+
+        assert_eq!(Some(Literal::Number(12.5)), tokens[0].literal);
+        assert_eq!(Some(Literal::Str(String::from("hi"))), tokens[1].literal);
+        assert_eq!(None, tokens[2].literal);
+    }
+
+    #[test]
+    fn keywords_can_ignore_case() {
+        let mut scanner = Scanner::new(String::from("Print NIL")).ignore_keyword_case();
+        let tokens = scanner.scan().unwrap();
+
+        assert_eq!(TokenKind::Print, tokens[0].kind);
+        assert_eq!("Print", tokens[0].lexeme);
+        assert_eq!(TokenKind::Nil, tokens[1].kind);
+
+        let mut scanner = Scanner::new(String::from("Print"));
+        assert_eq!(TokenKind::Identifier, scanner.scan().unwrap()[0].kind);
+    }
+
+    #[test]
+    fn unterminated_string_becomes_an_error_token() {
+        let mut scanner = Scanner::new(String::from("1 \"abc"));
+        let errors = scanner.scan().unwrap_err();
+
+        assert_eq!(1, errors.len());
+        assert_eq!(TokenKind::Error, scanner.tokens()[1].kind);
+        assert_eq!("\"abc", scanner.tokens()[1].lexeme);
+        assert_eq!(Span::new(2, 6), scanner.tokens()[1].span);
+    }
+
+    #[test]
+    fn trivia_tokens_cover_the_whole_source() {
+        let pieces = [
+            "a", "1", ".", "\"", "/", "//", " ", "\n", "\t", "$", "é", "#!", "=", "!", "\u{c}",
+        ];
+        let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..300 {
+            let mut source = String::new();
+            for _ in 0..12 {
+                source.push_str(pieces[rng.below(pieces.len())]);
+            }
+
+            let mut scanner = Scanner::with_trivia(source.clone());
+            let _ = scanner.scan();
+
+            let mut position = 0;
+            for token in scanner.tokens() {
+                assert_eq!(position, token.span.start, "source: {:?}", source);
+                position = token.span.end;
+            }
+            assert_eq!(source.chars().count(), position, "source: {:?}", source);
+            assert_eq!(TokenKind::Eof, scanner.tokens().last().unwrap().kind);
+        }
+    }
+
+    #[test]
+    fn scan_records_spans() {
+        let mut scanner = Scanner::new(String::from("foo >= \"bar\""));
+        let tokens = scanner.scan().unwrap();
+
+        assert_eq!(Span::new(0, 3), tokens[0].span);
+        assert_eq!(Span::new(4, 6), tokens[1].span);
+        assert_eq!(Span::new(7, 12), tokens[2].span);
+        assert_eq!(Span::new(12, 12), tokens[3].span);
     }
 
     #[test]
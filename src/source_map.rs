@@ -0,0 +1,251 @@
+// Identifies one source text added to a SourceMap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceId(usize);
+
+// What a column counts. Spans count characters; editors speaking LSP count
+// UTF-16 code units, so a character outside the BMP takes two columns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Columns {
+    Chars,
+    Utf16,
+}
+
+impl Columns {
+    fn width(self, c: char) -> usize {
+        match self {
+            Columns::Chars => 1,
+            Columns::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+struct SourceFile {
+    name: String,
+    text: String,
+}
+
+// Every source text the interpreter has seen, with the name to report it
+// under: a file path, or something like `<repl>` for input with no file.
+// Errors record the SourceId they came from, so a diagnostic can always say
+// which file a span points into.
+//
+// A name is only stored once: adding it again (the next REPL line, a file
+// re-run by --watch) replaces its text and keeps its id, so long sessions
+// don't keep every version of a source around.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: &str, text: &str) -> SourceId {
+        if let Some(i) = self.files.iter().position(|file| file.name == name) {
+            self.files[i].text = String::from(text);
+            return SourceId(i);
+        }
+
+        self.files.push(SourceFile {
+            name: String::from(name),
+            text: String::from(text),
+        });
+        SourceId(self.files.len() - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn name(&self, id: SourceId) -> &str {
+        &self.files[id.0].name
+    }
+
+    pub fn text(&self, id: SourceId) -> &str {
+        &self.files[id.0].text
+    }
+
+    // The 1-based line and column of the character at `offset`.
+    pub fn location(&self, id: SourceId, offset: usize) -> (usize, usize) {
+        let (line, column) = self.offset_to_line_col(id, offset, Columns::Chars);
+        (line + 1, column + 1)
+    }
+
+    // The 0-based line and column, LSP style, of the character at `offset`.
+    pub fn offset_to_line_col(
+        &self,
+        id: SourceId,
+        offset: usize,
+        columns: Columns,
+    ) -> (usize, usize) {
+        let mut line = 0;
+        let mut column = 0;
+        for c in self.text(id).chars().take(offset) {
+            if c == '\n' {
+                line += 1;
+                column = 0;
+            } else {
+                column += columns.width(c);
+            }
+        }
+        (line, column)
+    }
+
+    // The character offset of a 0-based line and column. A column past the
+    // end of its line means the end of the line, and one inside a UTF-16
+    // surrogate pair the character after it; a line past the end of the
+    // source has no offset.
+    pub fn line_col_to_offset(
+        &self,
+        id: SourceId,
+        line: usize,
+        column: usize,
+        columns: Columns,
+    ) -> Option<usize> {
+        let mut current_line = 0;
+        let mut current_column = 0;
+        let mut end = 0;
+        for (offset, c) in self.text(id).chars().enumerate() {
+            if current_line == line && (current_column >= column || c == '\n') {
+                return Some(offset);
+            }
+            if c == '\n' {
+                current_line += 1;
+                current_column = 0;
+            } else {
+                current_column += columns.width(c);
+            }
+            end = offset + 1;
+        }
+
+        if current_line == line {
+            Some(end)
+        } else {
+            None
+        }
+    }
+}
+
+// Turns the raw bytes of a source file into text. Lox source must be UTF-8;
+// anything else is rejected with the byte offset (and line) of the first
+// bad byte, so it can be found with a hex editor. A leading byte order mark
+// is dropped, so it doesn't shift the columns of the first line.
+pub fn decode(bytes: Vec<u8>) -> Result<String, String> {
+    let text = String::from_utf8(bytes).map_err(|e| {
+        let offset = e.utf8_error().valid_up_to();
+        let bytes = e.as_bytes();
+        let line = 1 + bytes[..offset].iter().filter(|b| **b == b'\n').count();
+        format!(
+            "Invalid UTF-8 at byte offset {} (line {}): 0x{:02X}",
+            offset, line, bytes[offset]
+        )
+    })?;
+
+    match text.strip_prefix('\u{feff}') {
+        Some(text) => Ok(String::from(text)),
+        None => Ok(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_keep_their_names() {
+        let mut sources = SourceMap::new();
+        let main = sources.add("main.lox", "1 + 2");
+        let repl = sources.add("<repl>", "nil");
+
+        assert_ne!(main, repl);
+        assert_eq!("main.lox", sources.name(main));
+        assert_eq!("nil", sources.text(repl));
+    }
+
+    #[test]
+    fn adding_a_name_again_replaces_its_text() {
+        let mut sources = SourceMap::new();
+        let first = sources.add("<repl>", "1");
+        let second = sources.add("<repl>", "2");
+
+        assert_eq!(first, second);
+        assert_eq!(1, sources.len());
+        assert_eq!("2", sources.text(second));
+    }
+
+    #[test]
+    fn location_counts_lines_and_characters() {
+        let mut sources = SourceMap::new();
+        let id = sources.add("a.lox", "1\n\"é\" + 2\n");
+
+        assert_eq!((1, 1), sources.location(id, 0));
+        assert_eq!((2, 1), sources.location(id, 2));
+        assert_eq!((2, 5), sources.location(id, 6));
+        assert_eq!((3, 1), sources.location(id, 10));
+    }
+
+    #[test]
+    fn positions_convert_both_ways() {
+        let mut sources = SourceMap::new();
+        let id = sources.add("a.lox", "\"😀\" + 1\nnil");
+
+        assert_eq!((0, 5), sources.offset_to_line_col(id, 4, Columns::Utf16));
+        assert_eq!((0, 4), sources.offset_to_line_col(id, 4, Columns::Chars));
+        assert_eq!((1, 1), sources.offset_to_line_col(id, 9, Columns::Utf16));
+
+        for offset in 0..=11 {
+            for &columns in [Columns::Chars, Columns::Utf16].iter() {
+                let (line, column) = sources.offset_to_line_col(id, offset, columns);
+                assert_eq!(
+                    Some(offset),
+                    sources.line_col_to_offset(id, line, column, columns)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn line_col_to_offset_clamps_columns() {
+        let mut sources = SourceMap::new();
+        let id = sources.add("a.lox", "\"😀\"\nnil");
+
+        assert_eq!(
+            Some(2),
+            sources.line_col_to_offset(id, 0, 2, Columns::Utf16)
+        );
+        assert_eq!(
+            Some(3),
+            sources.line_col_to_offset(id, 0, 40, Columns::Utf16)
+        );
+        assert_eq!(
+            Some(7),
+            sources.line_col_to_offset(id, 1, 40, Columns::Chars)
+        );
+        assert_eq!(None, sources.line_col_to_offset(id, 2, 0, Columns::Chars));
+    }
+
+    #[test]
+    fn decode_accepts_utf8() {
+        assert_eq!(
+            Ok(String::from("\"é\"")),
+            decode("\"é\"".as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn decode_drops_a_byte_order_mark() {
+        assert_eq!(Ok(String::from("nil")), decode(b"\xef\xbb\xbfnil".to_vec()));
+    }
+
+    #[test]
+    fn decode_reports_the_first_bad_byte() {
+        let err = decode(b"1 +\n\"caf\xe9\"".to_vec()).unwrap_err();
+        assert_eq!("Invalid UTF-8 at byte offset 8 (line 2): 0xE9", err);
+    }
+}
@@ -0,0 +1,67 @@
+// Helpers shared by the unit tests.
+use crate::expression::{Expression, ExpressionKind};
+use crate::lox_err::LoxErr;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::token::{Span, Token, TokenKind};
+
+// xorshift; deterministic so a failing case can be reproduced.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    // A number in 0..n.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+// Scans and parses `source`, which must scan without errors.
+pub(crate) fn parse(source: &str) -> Result<Expression, LoxErr> {
+    let mut scanner = Scanner::new(String::from(source));
+    let tokens = scanner.scan().unwrap().to_vec();
+    Parser::new(tokens).parse()
+}
+
+// Builders for expected trees; every node gets an empty span.
+pub(crate) fn node(kind: ExpressionKind) -> Expression {
+    Expression::new(kind, Span::default())
+}
+
+pub(crate) fn operator(kind: TokenKind, lexeme: &str) -> Token {
+    Token::new(kind, String::from(lexeme), 1, Span::default())
+}
+
+pub(crate) fn number(n: f64) -> Expression {
+    node(ExpressionKind::NumberLiteral(n))
+}
+
+pub(crate) fn binary(
+    left: Expression,
+    kind: TokenKind,
+    lexeme: &str,
+    right: Expression,
+) -> Expression {
+    node(ExpressionKind::Binary {
+        left: Box::new(left),
+        operator: operator(kind, lexeme),
+        right: Box::new(right),
+    })
+}
+
+pub(crate) fn unary(kind: TokenKind, lexeme: &str, right: Expression) -> Expression {
+    node(ExpressionKind::Unary {
+        operator: operator(kind, lexeme),
+        right: Box::new(right),
+    })
+}
+
+pub(crate) fn grouping(inner: Expression) -> Expression {
+    node(ExpressionKind::Grouping(Box::new(inner)))
+}
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum TokenKind {
@@ -48,31 +49,194 @@ pub enum TokenKind {
     Var,
     While,
 
+    // Trivia, only emitted by Scanner::with_trivia().
+    Whitespace,
+    Comment,
+
+    // Characters the scanner could not make sense of.
+    Error,
+
     Eof,
 }
 
+// Every reserved word, sorted so lookups can binary search.
+static KEYWORDS: [(&str, TokenKind); 16] = [
+    ("and", TokenKind::And),
+    ("class", TokenKind::Class),
+    ("else", TokenKind::Else),
+    ("false", TokenKind::False),
+    ("for", TokenKind::For),
+    ("fun", TokenKind::Fun),
+    ("if", TokenKind::If),
+    ("nil", TokenKind::Nil),
+    ("or", TokenKind::Or),
+    ("print", TokenKind::Print),
+    ("return", TokenKind::Return),
+    ("super", TokenKind::Super),
+    ("this", TokenKind::This),
+    ("true", TokenKind::True),
+    ("var", TokenKind::Var),
+    ("while", TokenKind::While),
+];
+
 impl TokenKind {
     pub fn reserve_kind(lexeme: &str) -> Option<TokenKind> {
-        match lexeme {
-            "and" => Some(TokenKind::And),
-            "class" => Some(TokenKind::Class),
-            "else" => Some(TokenKind::Else),
-            "false" => Some(TokenKind::False),
-            "for" => Some(TokenKind::For),
-            "fun" => Some(TokenKind::Fun),
-            "if" => Some(TokenKind::If),
-            "nil" => Some(TokenKind::Nil),
-            "or" => Some(TokenKind::Or),
-            "print" => Some(TokenKind::Print),
-            "return" => Some(TokenKind::Return),
-            "super" => Some(TokenKind::Super),
-            "this" => Some(TokenKind::This),
-            "true" => Some(TokenKind::True),
-            "var" => Some(TokenKind::Var),
-            "while" => Some(TokenKind::While),
-            _ => None,
+        KEYWORDS
+            .binary_search_by_key(&lexeme, |(word, _)| word)
+            .ok()
+            .map(|i| KEYWORDS[i].1.clone())
+    }
+
+    // Like `reserve_kind`, but `PRINT` and `Print` count as `print` too.
+    pub fn reserve_kind_ignoring_case(lexeme: &str) -> Option<TokenKind> {
+        TokenKind::reserve_kind(&lexeme.to_ascii_lowercase())
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        KEYWORDS.iter().any(|(_, kind)| kind == self)
+    }
+
+    // The name jlox prints for this kind (`LEFT_PAREN`, `STRING`, ...), so
+    // token dumps can be diffed against the reference implementation.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::LeftParen => "LEFT_PAREN",
+            TokenKind::RightParen => "RIGHT_PAREN",
+            TokenKind::LeftBrace => "LEFT_BRACE",
+            TokenKind::RightBrace => "RIGHT_BRACE",
+            TokenKind::Comma => "COMMA",
+            TokenKind::Dot => "DOT",
+            TokenKind::Minus => "MINUS",
+            TokenKind::Plus => "PLUS",
+            TokenKind::Semicolon => "SEMICOLON",
+            TokenKind::Slash => "SLASH",
+            TokenKind::Star => "STAR",
+            TokenKind::Bang => "BANG",
+            TokenKind::BangEqual => "BANG_EQUAL",
+            TokenKind::Equal => "EQUAL",
+            TokenKind::EqualEqual => "EQUAL_EQUAL",
+            TokenKind::Greater => "GREATER",
+            TokenKind::GreaterEqual => "GREATER_EQUAL",
+            TokenKind::Less => "LESS",
+            TokenKind::LessEqual => "LESS_EQUAL",
+            TokenKind::Identifier => "IDENTIFIER",
+            TokenKind::Str => "STRING",
+            TokenKind::Number => "NUMBER",
+            TokenKind::And => "AND",
+            TokenKind::Class => "CLASS",
+            TokenKind::Else => "ELSE",
+            TokenKind::False => "FALSE",
+            TokenKind::Fun => "FUN",
+            TokenKind::For => "FOR",
+            TokenKind::If => "IF",
+            TokenKind::Nil => "NIL",
+            TokenKind::Or => "OR",
+            TokenKind::Print => "PRINT",
+            TokenKind::Return => "RETURN",
+            TokenKind::Super => "SUPER",
+            TokenKind::This => "THIS",
+            TokenKind::True => "TRUE",
+            TokenKind::Var => "VAR",
+            TokenKind::While => "WHILE",
+            TokenKind::Whitespace => "WHITESPACE",
+            TokenKind::Comment => "COMMENT",
+            TokenKind::Error => "ERROR",
+            TokenKind::Eof => "EOF",
         }
     }
+
+    // The reserved words in alphabetical order.
+    pub fn all_keywords() -> impl Iterator<Item = &'static str> {
+        KEYWORDS.iter().map(|(word, _)| *word)
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for TokenKind {
+    type Err = String;
+
+    // The inverse of name(); keep the two in step.
+    fn from_str(name: &str) -> Result<TokenKind, String> {
+        Ok(match name {
+            "LEFT_PAREN" => TokenKind::LeftParen,
+            "RIGHT_PAREN" => TokenKind::RightParen,
+            "LEFT_BRACE" => TokenKind::LeftBrace,
+            "RIGHT_BRACE" => TokenKind::RightBrace,
+            "COMMA" => TokenKind::Comma,
+            "DOT" => TokenKind::Dot,
+            "MINUS" => TokenKind::Minus,
+            "PLUS" => TokenKind::Plus,
+            "SEMICOLON" => TokenKind::Semicolon,
+            "SLASH" => TokenKind::Slash,
+            "STAR" => TokenKind::Star,
+            "BANG" => TokenKind::Bang,
+            "BANG_EQUAL" => TokenKind::BangEqual,
+            "EQUAL" => TokenKind::Equal,
+            "EQUAL_EQUAL" => TokenKind::EqualEqual,
+            "GREATER" => TokenKind::Greater,
+            "GREATER_EQUAL" => TokenKind::GreaterEqual,
+            "LESS" => TokenKind::Less,
+            "LESS_EQUAL" => TokenKind::LessEqual,
+            "IDENTIFIER" => TokenKind::Identifier,
+            "STRING" => TokenKind::Str,
+            "NUMBER" => TokenKind::Number,
+            "AND" => TokenKind::And,
+            "CLASS" => TokenKind::Class,
+            "ELSE" => TokenKind::Else,
+            "FALSE" => TokenKind::False,
+            "FUN" => TokenKind::Fun,
+            "FOR" => TokenKind::For,
+            "IF" => TokenKind::If,
+            "NIL" => TokenKind::Nil,
+            "OR" => TokenKind::Or,
+            "PRINT" => TokenKind::Print,
+            "RETURN" => TokenKind::Return,
+            "SUPER" => TokenKind::Super,
+            "THIS" => TokenKind::This,
+            "TRUE" => TokenKind::True,
+            "VAR" => TokenKind::Var,
+            "WHILE" => TokenKind::While,
+            "WHITESPACE" => TokenKind::Whitespace,
+            "COMMENT" => TokenKind::Comment,
+            "ERROR" => TokenKind::Error,
+            "EOF" => TokenKind::Eof,
+            _ => return Err(format!("Unknown token kind: {}", name)),
+        })
+    }
+}
+
+// Character offsets into the scanned source, end exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    // The smallest span covering both.
+    pub fn merge(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+// The value a literal token stands for, worked out once by the scanner.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Number(f64),
+    Str(String),
 }
 
 #[derive(Clone, Debug)]
@@ -80,16 +244,33 @@ pub struct Token {
     pub kind: TokenKind,
     pub lexeme: String,
     pub line: usize,
+    pub span: Span,
+    pub literal: Option<Literal>,
 }
 
 impl Token {
-    pub fn new(kind: TokenKind, lexeme: String, line: usize) -> Token {
+    pub fn new(kind: TokenKind, lexeme: String, line: usize, span: Span) -> Token {
         Token {
-            kind: kind,
-            lexeme: lexeme,
-            line: line,
+            kind,
+            lexeme,
+            line,
+            span,
+            literal: None,
         }
     }
+
+    pub fn with_literal(mut self, literal: Literal) -> Token {
+        self.literal = Some(literal);
+        self
+    }
+}
+
+// Tokens compare by what they say, not where they were found, so trees
+// parsed from differently laid out source can still be equal.
+impl PartialEq for Token {
+    fn eq(&self, other: &Token) -> bool {
+        self.kind == other.kind && self.lexeme == other.lexeme
+    }
 }
 
 impl fmt::Display for Token {
@@ -104,10 +285,126 @@ mod tests {
 
     #[test]
     fn new() {
-        let token = Token::new(TokenKind::Eof, String::from("hehe"), 10);
+        let token = Token::new(TokenKind::Eof, String::from("hehe"), 10, Span::new(3, 7));
 
         assert_eq!(TokenKind::Eof, token.kind);
         assert_eq!(String::from("hehe"), token.lexeme);
         assert_eq!(10, token.line);
+        assert_eq!(Span::new(3, 7), token.span);
+        assert_eq!(None, token.literal);
+    }
+
+    #[test]
+    fn reserve_kind() {
+        assert_eq!(Some(TokenKind::And), TokenKind::reserve_kind("and"));
+        assert_eq!(Some(TokenKind::While), TokenKind::reserve_kind("while"));
+        assert_eq!(None, TokenKind::reserve_kind("While"));
+        assert_eq!(None, TokenKind::reserve_kind("whale"));
+        assert_eq!(
+            Some(TokenKind::While),
+            TokenKind::reserve_kind_ignoring_case("WHILE")
+        );
+    }
+
+    #[test]
+    fn keywords_are_sorted_and_complete() {
+        let words: Vec<&str> = TokenKind::all_keywords().collect();
+        let mut sorted = words.clone();
+        sorted.sort_unstable();
+
+        assert_eq!(sorted, words);
+        for word in words {
+            assert!(TokenKind::reserve_kind(word).unwrap().is_keyword());
+        }
+        assert!(!TokenKind::Identifier.is_keyword());
+    }
+
+    // Every kind.
+    const KINDS: [TokenKind; 42] = [
+        TokenKind::LeftParen,
+        TokenKind::RightParen,
+        TokenKind::LeftBrace,
+        TokenKind::RightBrace,
+        TokenKind::Comma,
+        TokenKind::Dot,
+        TokenKind::Minus,
+        TokenKind::Plus,
+        TokenKind::Semicolon,
+        TokenKind::Slash,
+        TokenKind::Star,
+        TokenKind::Bang,
+        TokenKind::BangEqual,
+        TokenKind::Equal,
+        TokenKind::EqualEqual,
+        TokenKind::Greater,
+        TokenKind::GreaterEqual,
+        TokenKind::Less,
+        TokenKind::LessEqual,
+        TokenKind::Identifier,
+        TokenKind::Str,
+        TokenKind::Number,
+        TokenKind::And,
+        TokenKind::Class,
+        TokenKind::Else,
+        TokenKind::False,
+        TokenKind::Fun,
+        TokenKind::For,
+        TokenKind::If,
+        TokenKind::Nil,
+        TokenKind::Or,
+        TokenKind::Print,
+        TokenKind::Return,
+        TokenKind::Super,
+        TokenKind::This,
+        TokenKind::True,
+        TokenKind::Var,
+        TokenKind::While,
+        TokenKind::Whitespace,
+        TokenKind::Comment,
+        TokenKind::Error,
+        TokenKind::Eof,
+    ];
+
+    #[test]
+    fn kind_names_round_trip() {
+        assert_eq!("RIGHT_PAREN", TokenKind::RightParen.to_string());
+        assert_eq!("BANG_EQUAL", TokenKind::BangEqual.to_string());
+        assert_eq!("STRING", TokenKind::Str.to_string());
+        assert_eq!("EOF", TokenKind::Eof.to_string());
+
+        for kind in KINDS.iter() {
+            assert_eq!(Ok(kind.clone()), kind.name().parse::<TokenKind>());
+        }
+        assert!("RightParen".parse::<TokenKind>().is_err());
+    }
+
+    #[test]
+    fn span_merge() {
+        assert_eq!(Span::new(2, 9), Span::new(2, 4).merge(Span::new(6, 9)));
+        assert_eq!(Span::new(2, 9), Span::new(6, 9).merge(Span::new(2, 4)));
+    }
+
+    #[test]
+    fn span_contains_is_end_exclusive() {
+        let span = Span::new(2, 4);
+
+        assert!(!span.contains(1));
+        assert!(span.contains(2));
+        assert!(span.contains(3));
+        assert!(!span.contains(4));
+    }
+
+    #[test]
+    fn eq_ignores_position() {
+        let token = Token::new(TokenKind::Plus, String::from("+"), 1, Span::new(0, 1));
+
+        assert_eq!(
+            token,
+            Token::new(TokenKind::Plus, String::from("+"), 3, Span::new(8, 9))
+        );
+        assert_ne!(
+            token,
+            Token::new(TokenKind::Minus, String::from("-"), 1, Span::new(0, 1))
+        );
     }
 }
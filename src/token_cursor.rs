@@ -0,0 +1,171 @@
+use crate::lox_err::LoxErr;
+use crate::token::{Span, Token, TokenKind};
+
+// A position in a scanned token stream, shared by anything that consumes
+// tokens. The stream ends with an Eof token (one is made up if the tokens
+// passed in lack it), and the cursor never moves past it.
+pub struct TokenCursor {
+    tokens: Vec<Token>,
+    eof: Token,
+    current: usize,
+}
+
+impl TokenCursor {
+    pub fn new(mut tokens: Vec<Token>) -> TokenCursor {
+        let eof = match tokens.pop() {
+            Some(token) if token.kind == TokenKind::Eof => token,
+            last => {
+                let (line, end) = last.as_ref().map_or((1, 0), |t| (t.line, t.span.end));
+                tokens.extend(last);
+                Token::new(TokenKind::Eof, String::new(), line, Span::new(end, end))
+            }
+        };
+
+        TokenCursor {
+            tokens,
+            eof,
+            current: 0,
+        }
+    }
+
+    pub fn peek(&self) -> &Token {
+        self.peek_nth(0)
+    }
+
+    // Looks `n` tokens ahead without consuming anything; past the end of the
+    // stream this keeps returning the Eof token.
+    pub fn peek_nth(&self, n: usize) -> &Token {
+        self.tokens.get(self.current + n).unwrap_or(&self.eof)
+    }
+
+    pub fn previous(&self) -> &Token {
+        self.tokens
+            .get(self.current.saturating_sub(1))
+            .unwrap_or(&self.eof)
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.peek().kind == TokenKind::Eof
+    }
+
+    pub fn check(&self, kind: &TokenKind) -> bool {
+        !self.is_at_end() && self.peek().kind == *kind
+    }
+
+    pub fn advance(&mut self) -> Token {
+        if self.is_at_end() {
+            return self.peek().clone();
+        }
+
+        self.current += 1;
+        self.previous().clone()
+    }
+
+    // Consumes the next token if it is any of `kinds`.
+    pub fn eat(&mut self, kinds: &[TokenKind]) -> Option<Token> {
+        if kinds.iter().any(|kind| self.check(kind)) {
+            Some(self.advance())
+        } else {
+            None
+        }
+    }
+
+    // Consumes the next token, which must be `kind`.
+    pub fn expect(&mut self, kind: TokenKind) -> Result<Token, LoxErr> {
+        if self.check(&kind) {
+            return Ok(self.advance());
+        }
+
+        let token = self.peek();
+        Err(LoxErr::new(
+            token.line,
+            format!("Unexpected token. expected: {}, got: {}", kind, token.kind),
+        )
+        .with_span(token.span))
+    }
+
+    // The span from the start of `first` to the end of the last consumed
+    // token, i.e. everything a construct beginning at `first` covered.
+    pub fn span_from(&self, first: &Token) -> Span {
+        first.span.merge(self.previous().span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn cursor(source: &str) -> TokenCursor {
+        let mut scanner = Scanner::new(String::from(source));
+        TokenCursor::new(scanner.scan().unwrap().to_vec())
+    }
+
+    #[test]
+    fn peek_nth_looks_ahead_without_consuming() {
+        let cursor = cursor("1 + 2");
+
+        assert_eq!(TokenKind::Number, cursor.peek_nth(0).kind);
+        assert_eq!(TokenKind::Plus, cursor.peek_nth(1).kind);
+        assert_eq!(TokenKind::Eof, cursor.peek_nth(3).kind);
+        assert_eq!(TokenKind::Eof, cursor.peek_nth(10).kind);
+        assert_eq!(TokenKind::Number, cursor.peek().kind);
+    }
+
+    #[test]
+    fn missing_eof_is_added() {
+        let mut cursor = TokenCursor::new(vec![]);
+        assert!(cursor.is_at_end());
+        assert_eq!(TokenKind::Eof, cursor.advance().kind);
+        assert_eq!(TokenKind::Eof, cursor.previous().kind);
+
+        let one = Token::new(TokenKind::Nil, String::from("nil"), 3, Span::new(4, 7));
+        let mut cursor = TokenCursor::new(vec![one]);
+        assert_eq!(TokenKind::Nil, cursor.advance().kind);
+        assert_eq!(TokenKind::Eof, cursor.peek().kind);
+        assert_eq!(3, cursor.peek().line);
+        assert_eq!(Span::new(7, 7), cursor.peek().span);
+    }
+
+    #[test]
+    fn advance_stops_at_eof() {
+        let mut cursor = cursor("1");
+
+        assert_eq!(TokenKind::Number, cursor.advance().kind);
+        assert_eq!(TokenKind::Eof, cursor.advance().kind);
+        assert_eq!(TokenKind::Eof, cursor.advance().kind);
+        assert!(cursor.is_at_end());
+    }
+
+    #[test]
+    fn eat_consumes_only_matching_tokens() {
+        let mut cursor = cursor("- 1");
+
+        assert!(cursor.eat(&[TokenKind::Plus]).is_none());
+        let token = cursor.eat(&[TokenKind::Plus, TokenKind::Minus]).unwrap();
+        assert_eq!(TokenKind::Minus, token.kind);
+        assert_eq!(TokenKind::Number, cursor.peek().kind);
+    }
+
+    #[test]
+    fn expect_reports_the_unexpected_token() {
+        let mut cursor = cursor("(1");
+        cursor.advance();
+        cursor.advance();
+
+        let err = cursor.expect(TokenKind::RightParen).unwrap_err();
+        assert!(format!("{}", err).contains("expected: RIGHT_PAREN, got: EOF"));
+    }
+
+    #[test]
+    fn span_from_covers_consumed_tokens() {
+        let mut cursor = cursor("(1 + 2) * 3");
+        let first = cursor.advance();
+        cursor.advance();
+        cursor.advance();
+        cursor.advance();
+        cursor.advance();
+
+        assert_eq!(Span::new(0, 7), cursor.span_from(&first));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::token::{Literal, Token};
+
+// Token dumps for people and for tools. Both list every token in order,
+// including Error and Eof tokens, so a dump of bad input still shows where
+// scanning went wrong.
+
+// One token per line: `<line> <KIND> <lexeme>`, with the lexeme quoted.
+pub fn text(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| format!("{} {} {:?}\n", token.line, token.kind, token.lexeme))
+        .collect()
+}
+
+// A JSON array with one object per token:
+//
+//   {"kind": "NUMBER", "lexeme": "1", "literal": 1, "line": 1, "column": 1,
+//    "span": {"start": 0, "end": 1}}
+//
+// `kind` uses the jlox names and `literal` is a number, a string or null;
+// a number literal too large for an f64 is null, as JSON has no infinity.
+// `line` and `column` are where the token starts, counting from 1, so a
+// string spanning lines reports its opening quote. `span` is in characters
+// from 0.
+// Fields are only ever added to this schema, never renamed or removed.
+pub fn json(source: &str, tokens: &[Token]) -> String {
+    let starts = line_starts(source);
+    let mut output = String::from("[\n");
+
+    for (i, token) in tokens.iter().enumerate() {
+        let line = starts.partition_point(|s| *s <= token.span.start);
+        let line_start = starts[line - 1];
+        let literal = match &token.literal {
+            Some(Literal::Number(n)) if n.is_finite() => format!("{}", n),
+            Some(Literal::Number(_)) => String::from("null"),
+            Some(Literal::Str(s)) => json_string(s),
+            None => String::from("null"),
+        };
+
+        output.push_str(&format!(
+            "  {{\"kind\": {}, \"lexeme\": {}, \"literal\": {}, \"line\": {}, \"column\": {}, \
+             \"span\": {{\"start\": {}, \"end\": {}}}}}",
+            json_string(&token.kind.to_string()),
+            json_string(&token.lexeme),
+            literal,
+            line,
+            token.span.start - line_start + 1,
+            token.span.start,
+            token.span.end
+        ));
+        output.push_str(if i + 1 < tokens.len() { ",\n" } else { "\n" });
+    }
+
+    output.push_str("]\n");
+    output
+}
+
+// The character offset each line starts at.
+fn line_starts(source: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(
+        source
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| *c == '\n')
+            .map(|(i, _)| i + 1),
+    );
+    starts
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn scan(source: &str) -> Vec<Token> {
+        let mut scanner = Scanner::new(String::from(source));
+        let _ = scanner.scan();
+        scanner.tokens().to_vec()
+    }
+
+    #[test]
+    fn text_lists_line_kind_and_lexeme() {
+        assert_eq!(
+            "1 NUMBER \"1\"\n1 PLUS \"+\"\n2 STRING \"a\"\n2 EOF \"\"\n",
+            text(&scan("1 +\n\"a\""))
+        );
+    }
+
+    #[test]
+    fn json_describes_each_token() {
+        let source = "1.5\n  \"a\\\"b\" $";
+        assert_eq!(
+            "[\n  \
+             {\"kind\": \"NUMBER\", \"lexeme\": \"1.5\", \"literal\": 1.5, \"line\": 1, \"column\": 1, \"span\": {\"start\": 0, \"end\": 3}},\n  \
+             {\"kind\": \"STRING\", \"lexeme\": \"a\\\\\", \"literal\": \"a\\\\\", \"line\": 2, \"column\": 3, \"span\": {\"start\": 6, \"end\": 10}},\n  \
+             {\"kind\": \"IDENTIFIER\", \"lexeme\": \"b\", \"literal\": null, \"line\": 2, \"column\": 7, \"span\": {\"start\": 10, \"end\": 11}},\n  \
+             {\"kind\": \"ERROR\", \"lexeme\": \"\\\" $\", \"literal\": null, \"line\": 2, \"column\": 8, \"span\": {\"start\": 11, \"end\": 14}},\n  \
+             {\"kind\": \"EOF\", \"lexeme\": \"\", \"literal\": null, \"line\": 2, \"column\": 11, \"span\": {\"start\": 14, \"end\": 14}}\n\
+             ]\n",
+            json(source, &scan(source))
+        );
+    }
+
+    #[test]
+    fn json_escapes_control_characters() {
+        assert_eq!("\"a\\tb\\u0001\"", json_string("a\tb\u{1}"));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::expression::{Expression, ExpressionKind};
+
+// A rewrite over expression trees. Implementors override `transform` for
+// the nodes they care about and call `transform_children` to carry on into
+// the rest; the default leaves every node as it was. Desugarings and
+// optimizer passes are written as transforms so the parser only ever has to
+// build the surface syntax.
+pub trait Transform {
+    fn transform(&mut self, expression: Expression) -> Expression {
+        transform_children(self, expression)
+    }
+}
+
+// Rebuilds `expression` with each of its children passed through
+// `transform`. The node itself, and its span, are kept.
+pub fn transform_children<T: Transform + ?Sized>(
+    transform: &mut T,
+    expression: Expression,
+) -> Expression {
+    let kind = match expression.kind {
+        ExpressionKind::Binary {
+            left,
+            operator,
+            right,
+        } => ExpressionKind::Binary {
+            left: Box::new(transform.transform(*left)),
+            operator,
+            right: Box::new(transform.transform(*right)),
+        },
+        ExpressionKind::Unary { operator, right } => ExpressionKind::Unary {
+            operator,
+            right: Box::new(transform.transform(*right)),
+        },
+        ExpressionKind::Grouping(e) => ExpressionKind::Grouping(Box::new(transform.transform(*e))),
+        kind => kind,
+    };
+    Expression::new(kind, expression.span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse;
+    use crate::token::{Span, TokenKind};
+
+    struct Identity;
+
+    impl Transform for Identity {}
+
+    // Drops parentheses, which only matter to the parser.
+    struct Ungroup;
+
+    impl Transform for Ungroup {
+        fn transform(&mut self, expression: Expression) -> Expression {
+            match expression.kind {
+                ExpressionKind::Grouping(e) => self.transform(*e),
+                kind => transform_children(self, Expression::new(kind, expression.span)),
+            }
+        }
+    }
+
+    // Folds arithmetic on number literals, bottom up.
+    struct FoldConstants;
+
+    impl Transform for FoldConstants {
+        fn transform(&mut self, expression: Expression) -> Expression {
+            let expression = transform_children(self, expression);
+            if let ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } = &expression.kind
+            {
+                if let (ExpressionKind::NumberLiteral(a), ExpressionKind::NumberLiteral(b)) =
+                    (&left.kind, &right.kind)
+                {
+                    let value = match operator.kind {
+                        TokenKind::Plus => Some(a + b),
+                        TokenKind::Minus => Some(a - b),
+                        TokenKind::Star => Some(a * b),
+                        TokenKind::Slash => Some(a / b),
+                        _ => None,
+                    };
+                    if let Some(value) = value {
+                        return Expression::new(
+                            ExpressionKind::NumberLiteral(value),
+                            expression.span,
+                        );
+                    }
+                }
+            }
+            expression
+        }
+    }
+
+    #[test]
+    fn default_transform_keeps_the_tree() {
+        let expression = parse("(1 + 2) * -\"a\" == nil").unwrap();
+        let copy = parse("(1 + 2) * -\"a\" == nil").unwrap();
+
+        assert_eq!(copy, Identity.transform(expression));
+    }
+
+    #[test]
+    fn transforms_can_remove_nodes() {
+        let expression = Ungroup.transform(parse("((1) + (2 * 3))").unwrap());
+
+        assert_eq!("(+ 1 (* 2 3))", format!("{}", expression));
+    }
+
+    #[test]
+    fn transforms_see_rewritten_children() {
+        let expression =
+            FoldConstants.transform(Ungroup.transform(parse("(1 + 2) * 4 < 6 / \"a\"").unwrap()));
+
+        assert_eq!("(< 12 (/ 6 'a'))", format!("{}", expression));
+    }
+
+    #[test]
+    fn spans_are_kept() {
+        let expression = Identity.transform(parse("1 + 2").unwrap());
+
+        assert_eq!(Span::new(0, 5), expression.span);
+        assert_eq!(Span::new(4, 5), expression.children()[1].span);
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Notices when a file has been saved, by polling its modification time.
+// Polling keeps this dependency-free and works the same everywhere; a
+// script being edited by hand doesn't need faster than a few checks a
+// second.
+pub struct Watcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    missing: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Unchanged,
+    Modified,
+    // The file could not be found; reported once until it comes back.
+    Removed,
+}
+
+impl Watcher {
+    pub fn new(path: impl Into<PathBuf>) -> Watcher {
+        Watcher {
+            path: path.into(),
+            modified: None,
+            missing: false,
+        }
+    }
+
+    // Modified the first time it is called, and after that whenever the
+    // file's modification time has moved since the last call. A file that
+    // goes missing is Removed once, and Modified again when it reappears.
+    pub fn changed(&mut self) -> Change {
+        let modified = match fs::metadata(&self.path).and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) if self.missing => return Change::Unchanged,
+            Err(_) => {
+                self.missing = true;
+                self.modified = None;
+                return Change::Removed;
+            }
+        };
+
+        self.missing = false;
+        if self.modified == Some(modified) {
+            return Change::Unchanged;
+        }
+        self.modified = Some(modified);
+        Change::Modified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+    use std::time::Duration;
+
+    #[test]
+    fn changed_tracks_the_modification_time() {
+        let path = env::temp_dir().join(format!("lox-watch-{}.lox", std::process::id()));
+        fs::write(&path, "1").unwrap();
+        let mut watcher = Watcher::new(&path);
+
+        assert_eq!(Change::Modified, watcher.changed());
+        assert_eq!(Change::Unchanged, watcher.changed());
+
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(Change::Modified, watcher.changed());
+        assert_eq!(Change::Unchanged, watcher.changed());
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Change::Removed, watcher.changed());
+        assert_eq!(Change::Unchanged, watcher.changed());
+
+        fs::write(&path, "2").unwrap();
+        assert_eq!(Change::Modified, watcher.changed());
+        fs::remove_file(&path).unwrap();
+    }
+}